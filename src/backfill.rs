@@ -0,0 +1,142 @@
+//! Fetching issues and events into the cache ahead of time
+
+use crate::cache::Cache;
+use crate::closings::Issues;
+use crate::source::IssueSource;
+use crate::triage::TriageQuery;
+use crate::{Error, Result};
+
+/// Fetching every date in a range that isn't cached completely yet
+///
+/// Dates that fail to fetch are skipped. Hitting a rate limit or running out
+/// of requests stops the backfill, and running it again continues where it
+/// stopped, as the dates fetched so far are cached.
+///
+/// ```no_run
+/// # async fn example(github: &triage_tracker::github::GitHub) -> triage_tracker::Result<()> {
+/// let cache = triage_tracker::Cache::in_memory()?;
+/// let from = chrono::NaiveDate::from_ymd(2021, 1, 1);
+/// let to = chrono::NaiveDate::from_ymd(2021, 6, 30);
+/// let report = triage_tracker::Backfill::new(from, to)?
+///     .run(github, github, &cache, |date| println!("Fetched {}", date))
+///     .await?;
+/// if let Some((date, error)) = report.stopped_at {
+///     println!("Stopped at {}: {}", date, error);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Backfill {
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+    triage: Option<TriageQuery>,
+}
+
+/// The outcome of running a `Backfill`
+#[derive(Debug)]
+pub struct BackfillReport {
+    /// The number of dates fetched
+    pub fetched: usize,
+    /// The number of dates that were already cached completely
+    pub cached: usize,
+    /// Dates that failed to fetch and were skipped, and why
+    pub failed: Vec<(chrono::NaiveDate, Error)>,
+    /// The date a rate limit or the request budget stopped the backfill at
+    ///
+    /// Later dates weren't fetched, and the triage query wasn't run.
+    pub stopped_at: Option<(chrono::NaiveDate, Error)>,
+    /// Why checking the activity of open issues stopped before it was done
+    pub triage_stopped: Option<Error>,
+}
+
+impl Backfill {
+    /// Fetch the dates from `from` to `to`, both included
+    ///
+    /// Dates are in UTC. A range reaching past today stops at today, and
+    /// one starting after today is an error.
+    pub fn new(from: chrono::NaiveDate, to: chrono::NaiveDate) -> Result<Self> {
+        if from > to {
+            return Err(format!(
+                "can't backfill from {} to {}: it ends before it starts",
+                from, to
+            )
+            .into());
+        }
+        // Dates are in UTC, where today may still be yesterday for users east of it
+        let today = chrono::Utc::today().naive_utc();
+        if from > today {
+            return Err(format!(
+                "can't backfill from {}: it is after today ({} in UTC)",
+                from, today
+            )
+            .into());
+        }
+        Ok(Self {
+            from,
+            to: to.min(today),
+            triage: None,
+        })
+    }
+
+    /// The last date that will be fetched, which is today if the range reached past it
+    pub fn to(&self) -> chrono::NaiveDate {
+        self.to
+    }
+
+    /// Also run `query` once every date is fetched
+    pub fn triage(mut self, query: TriageQuery) -> Self {
+        self.triage = Some(query);
+        self
+    }
+
+    /// Run the backfill, calling `on_fetched` with each date as it is fetched
+    ///
+    /// Opened and closed issues are looked up in `closings_source`, while the
+    /// triage query runs against `source`. Rate limits and running out of
+    /// requests are not errors, but returned in the report.
+    pub async fn run(
+        &self,
+        source: &dyn IssueSource,
+        closings_source: &dyn IssueSource,
+        cache: &Cache,
+        mut on_fetched: impl FnMut(chrono::NaiveDate),
+    ) -> Result<BackfillReport> {
+        let mut report = BackfillReport {
+            fetched: 0,
+            cached: 0,
+            failed: Vec::new(),
+            stopped_at: None,
+            triage_stopped: None,
+        };
+        let mut date = self.from;
+        while date <= self.to {
+            let is_cached = Issues::cached_for_date(closings_source, cache, date)?
+                .is_some_and(|issues| issues.is_complete());
+            if is_cached {
+                report.cached += 1;
+            } else {
+                match Issues::for_date(closings_source, cache, date).await {
+                    Ok(_) => {
+                        on_fetched(date);
+                        report.fetched += 1;
+                    }
+                    Err(e @ Error::RateLimited(_)) | Err(e @ Error::BudgetExhausted(_)) => {
+                        report.stopped_at = Some((date, e));
+                        return Ok(report);
+                    }
+                    Err(e) => report.failed.push((date, e)),
+                }
+            }
+            date = date.succ();
+        }
+        if let Some(query) = &self.triage {
+            match query.run(source, cache).await {
+                Ok(triage) => report.triage_stopped = triage.rate_limited.map(Error::RateLimited),
+                Err(e @ Error::BudgetExhausted(_)) => report.triage_stopped = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(report)
+    }
+}
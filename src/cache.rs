@@ -0,0 +1,1329 @@
+//! Caching of fetched data in a SQLite database
+//!
+//! Each repository gets its own database, `cache.sqlite3` in the repository's
+//! cache directory.
+//! Issues are stored once no matter how many dates or events they were seen
+//! with, and the `fetched` table records when the lists for a date were fetched
+//! and whether they were complete by then. Which issues belong to a date's list
+//! is recorded in `list_issues`, since the `issues` table also holds issues
+//! seen in events and triage queries.
+//!
+//! Several runs may share a cache, e.g. one started from cron and one by hand.
+//! All writes happen in transactions that take SQLite's write lock up front, so
+//! an interrupted run never leaves partial lists behind and concurrent runs wait
+//! for each other's writes instead of failing. Since stores are upserts, the data
+//! both runs fetched ends up merged.
+//!
+//! Versions before the database kept JSON files in `database/` below the current
+//! directory, which are imported once when a repository's database is created.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::debug;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::Serialize;
+
+use crate::triage::Activity;
+use crate::{Actor, Comment, Error, Event, EventId, Issue, PullRequest, Repo, Result};
+
+/// The tables as first created, before any later migrations
+///
+/// Databases created before schema versions were recorded have these tables
+/// already, hence the `IF NOT EXISTS`.
+const INITIAL_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS issues (
+    number INTEGER PRIMARY KEY,
+    title TEXT NOT NULL,
+    comments INTEGER NOT NULL,
+    is_pull_request INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    closed_at TEXT
+);
+CREATE INDEX IF NOT EXISTS issues_created_at ON issues (created_at);
+CREATE TABLE IF NOT EXISTS events (
+    issue INTEGER NOT NULL REFERENCES issues (number),
+    kind TEXT NOT NULL,
+    actor TEXT,
+    created_at TEXT NOT NULL,
+    source TEXT NOT NULL,
+    UNIQUE (issue, kind, created_at, source)
+);
+CREATE INDEX IF NOT EXISTS events_created_at ON events (created_at);
+CREATE TABLE IF NOT EXISTS comments (
+    issue INTEGER NOT NULL REFERENCES issues (number),
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE (issue, created_at)
+);
+CREATE TABLE IF NOT EXISTS fetched (
+    date TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    PRIMARY KEY (date, kind)
+);
+CREATE TABLE IF NOT EXISTS triage (
+    issue INTEGER PRIMARY KEY,
+    activity TEXT NOT NULL,
+    date TEXT NOT NULL,
+    last_checked TEXT NOT NULL
+);
+";
+
+/// The columns of an issue `i` read by `issue_from_row`
+const ISSUE_COLUMNS: &str =
+    "i.number, i.title, i.comments, i.is_pull_request, i.created_at, i.closed_at, i.author,
+    (SELECT json_group_array(name) FROM labels WHERE issue = i.number)";
+
+/// The steps bringing the database from one schema version to the next
+///
+/// A database's `user_version` is the number of migrations applied to it. New
+/// migrations are only ever appended, and released ones must not change.
+const MIGRATIONS: &[fn(&Connection) -> rusqlite::Result<()>] = &[
+    create_tables,
+    add_fetched_metadata,
+    add_labels,
+    add_issue_authors,
+    add_list_issues,
+];
+
+/// How long to wait for another run to finish writing to the cache
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How long after a date ends its lists are trusted to be complete
+///
+/// Issues and events keep being added until the day is over, and GitHub's
+/// events feed can lag behind. Lists fetched earlier than this are refetched
+/// and topped up rather than used as they are, which means today and yesterday
+/// are always fetched.
+const SETTLE_TIME_HOURS: i64 = 24;
+
+/// The lists of a date that can be cached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheType {
+    /// Issues created on the date, as the source lists them
+    Issues,
+    /// Closing and reopening events on the date
+    Events,
+    /// Issues in any state created on the date, found by searching
+    SearchIssues,
+    /// Closing events derived from search results
+    SearchEvents,
+}
+
+impl std::fmt::Display for CacheType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let typ = match self {
+            CacheType::Issues => "issues",
+            CacheType::Events => "events",
+            CacheType::SearchIssues => "search-issues",
+            CacheType::SearchEvents => "search-events",
+        };
+        f.write_str(typ)
+    }
+}
+
+impl std::str::FromStr for CacheType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "issues" => Ok(Self::Issues),
+            "events" => Ok(Self::Events),
+            "search-issues" => Ok(Self::SearchIssues),
+            "search-events" => Ok(Self::SearchEvents),
+            _ => Err(format!("unknown list '{}'", s)),
+        }
+    }
+}
+
+/// The items of a list read from the cache
+pub(crate) struct Cached<T> {
+    pub(crate) items: Vec<T>,
+    /// Whether the list was fetched after the date settled
+    pub(crate) complete: bool,
+}
+
+/// A date's list of issues or events in the cache
+#[derive(Debug, Clone)]
+pub struct CachedList {
+    /// The date the list is for
+    pub date: chrono::NaiveDate,
+    /// Which of the date's lists this is
+    pub kind: CacheType,
+    /// When the list was last fetched, unknown for lists cached by old versions
+    pub fetched_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the list was fetched after the date settled, so it isn't fetched again
+    pub complete: bool,
+}
+
+/// What was imported from a legacy cache by `Cache::import_legacy`
+#[derive(Debug, Clone, Default)]
+pub struct LegacyImport {
+    /// Lists of issues or events
+    pub lists: u64,
+    /// Issues whose activity was imported from `triage.json`
+    pub activities: u64,
+    /// Files that couldn't be read
+    pub skipped: u64,
+}
+
+/// What the cache holds
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    /// Cached issues
+    pub issues: u64,
+    /// Cached events
+    pub events: u64,
+    /// Cached comments
+    pub comments: u64,
+    /// Lists that are settled and aren't fetched again
+    pub complete_lists: u64,
+    /// Lists that are fetched again when next used
+    pub incomplete_lists: u64,
+    /// Issues whose activity was checked recently enough to be used as is
+    pub fresh_triage: u64,
+    /// Issues whose activity is checked again before being used
+    pub stale_triage: u64,
+    /// The size of the database in bytes
+    pub size: u64,
+}
+
+/// A repository's cache of issues, events, comments and triage state
+///
+/// ```no_run
+/// # fn example() -> triage_tracker::Result<()> {
+/// use triage_tracker::{github, Cache};
+///
+/// let repo = "rust-lang/cargo".parse()?;
+/// let cache = Cache::open(&Cache::default_dir("github", github::DEFAULT_API_URL, &repo)?)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Cache {
+    // `Connection` isn't `Sync`, and none of the queries hold the lock across an await
+    conn: Mutex<Connection>,
+    /// Whether the database was created when the cache was opened
+    created: bool,
+}
+
+impl Cache {
+    /// Open the cache in `dir`, creating the directory and database if needed
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            Error::from(format!(
+                "failed to create cache directory '{}': {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        let path = dir.join("cache.sqlite3");
+        debug!("Opening cache at '{}'", path.display());
+        Self::new(Connection::open(&path)?)
+    }
+
+    /// A cache that is gone when the process exits, for when there is nowhere to write
+    pub fn in_memory() -> Result<Self> {
+        debug!("Using an in-memory cache");
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut conn: Connection) -> Result<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // With write-ahead logging, reading doesn't block another run's writes and vice versa
+        let journal_mode: String =
+            conn.query_row("PRAGMA journal_mode = WAL", rusqlite::NO_PARAMS, |row| {
+                row.get(0)
+            })?;
+        debug!("Cache journal mode is '{}'", journal_mode);
+        let tx = write_transaction(&mut conn)?;
+        let version = migrate(&tx)?;
+        tx.commit()?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            created: version == 0,
+        })
+    }
+
+    /// The default cache directory for `repo` on `forge` at `api_url`:
+    /// `$XDG_CACHE_HOME/triage-tracker/<forge>/<host>/<owner>/<name>`
+    ///
+    /// `~/.cache` is used if `XDG_CACHE_HOME` is not set. A port in the API URL is
+    /// added to the host after an underscore, so that mock servers on the same host
+    /// don't share a cache.
+    pub fn default_dir(forge: &str, api_url: &str, repo: &Repo) -> Result<PathBuf> {
+        let base = match std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from) {
+            // The XDG spec says relative paths are to be ignored
+            Some(dir) if dir.is_absolute() => dir,
+            _ => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".cache"))
+                .ok_or("can't find a cache directory: neither XDG_CACHE_HOME nor HOME is set")?,
+        };
+        let url = reqwest::Url::parse(api_url)
+            .map_err(|e| format!("invalid API URL '{}': {}", api_url, e))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}_{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(format!("API URL '{}' has no host", api_url).into()),
+        };
+        Ok(base
+            .join("triage-tracker")
+            .join(forge)
+            .join(host)
+            .join(&repo.owner)
+            .join(&repo.name))
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The issues of `cache_type` created on `date`, if they have been stored
+    pub(crate) fn issues_on(
+        &self,
+        date: &chrono::NaiveDate,
+        cache_type: CacheType,
+    ) -> Result<Option<Cached<Issue>>> {
+        let conn = self.conn();
+        let complete = match fetched_state(&conn, date, cache_type)? {
+            Some(complete) => complete,
+            None => return Ok(None),
+        };
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM list_issues l JOIN issues i ON i.number = l.issue
+             WHERE l.date = ?1 AND l.kind = ?2
+             ORDER BY i.number",
+            ISSUE_COLUMNS
+        ))?;
+        let items = statement
+            .query_map(params![date.to_string(), cache_type.to_string()], |row| {
+                issue_from_row(row, 0)
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Some(Cached { items, complete }))
+    }
+
+    /// The events of `cache_type` on `date`, if they have been stored
+    pub(crate) fn events_on(
+        &self,
+        date: &chrono::NaiveDate,
+        cache_type: CacheType,
+    ) -> Result<Option<Cached<Event>>> {
+        let conn = self.conn();
+        let complete = match fetched_state(&conn, date, cache_type)? {
+            Some(complete) => complete,
+            None => return Ok(None),
+        };
+        let (start, end) = day_bounds(date);
+        let mut statement = conn.prepare(&format!(
+            "SELECT {}, e.kind, e.actor, e.created_at
+             FROM events e JOIN issues i ON i.number = e.issue
+             WHERE e.source = ?1 AND e.created_at >= ?2 AND e.created_at < ?3
+             ORDER BY e.created_at",
+            ISSUE_COLUMNS
+        ))?;
+        let items = statement
+            .query_map(params![cache_type.to_string(), start, end], |row| {
+                let kind: String = row.get(8)?;
+                Ok(Event {
+                    issue: issue_from_row(row, 0)?,
+                    id: match kind.as_str() {
+                        "closed" => EventId::Closed,
+                        "reopened" => EventId::Reopened,
+                        _ => EventId::Unknown,
+                    },
+                    actor: row
+                        .get::<_, Option<String>>(9)?
+                        .map(|login| Actor { login }),
+                    when: row.get(10)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Some(Cached { items, complete }))
+    }
+
+    /// The cached issues that were open when last seen, oldest first
+    pub(crate) fn open_issues(&self) -> Result<Vec<Issue>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {} FROM issues i WHERE i.closed_at IS NULL ORDER BY i.number",
+            ISSUE_COLUMNS
+        ))?;
+        let issues = statement
+            .query_map(rusqlite::NO_PARAMS, |row| issue_from_row(row, 0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(issues)
+    }
+
+    /// Store the complete list of issues of `cache_type` created on `date`
+    ///
+    /// The list replaces the one stored before, if any.
+    pub(crate) fn store_issues_on(
+        &self,
+        date: &chrono::NaiveDate,
+        issues: &[Issue],
+        cache_type: CacheType,
+    ) -> Result<()> {
+        debug!("Caching {} '{}' for '{}'", issues.len(), cache_type, date);
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        insert_issues_on(&tx, date, issues, cache_type, chrono::Utc::now())?;
+        Ok(tx.commit()?)
+    }
+
+    /// Store the complete list of events of `cache_type` on `date`
+    pub(crate) fn store_events_on(
+        &self,
+        date: &chrono::NaiveDate,
+        events: &[Event],
+        cache_type: CacheType,
+    ) -> Result<()> {
+        debug!("Caching {} '{}' for '{}'", events.len(), cache_type, date);
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        insert_events_on(&tx, date, events, cache_type, chrono::Utc::now())?;
+        Ok(tx.commit()?)
+    }
+
+    /// Store what is currently known about open issues
+    pub(crate) fn store_issues(&self, issues: &[Issue]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        for issue in issues {
+            upsert_issue(&tx, issue)?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    /// Store comments made on an issue
+    pub(crate) fn store_comments(&self, issue_number: u32, comments: &[Comment]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        for comment in comments {
+            tx.execute(
+                "INSERT OR REPLACE INTO comments (issue, body, created_at) VALUES (?1, ?2, ?3)",
+                params![issue_number, comment.body, comment.created_at],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    /// The activity last determined for an issue, and when that was
+    pub(crate) fn activity(
+        &self,
+        issue_number: u32,
+    ) -> Result<Option<(Activity, chrono::DateTime<chrono::Utc>)>> {
+        let conn = self.conn();
+        let row = conn
+            .query_row(
+                "SELECT activity, date, last_checked FROM triage WHERE issue = ?1",
+                params![issue_number],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, chrono::NaiveDate>(1)?,
+                        row.get(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        Ok(row.and_then(|(activity, date, last_checked)| {
+            let activity = match activity.as_str() {
+                "no-activity-since" => Activity::NoActivitySince(date),
+                "last-commented" => Activity::LastCommented(date),
+                _ => return None,
+            };
+            Some((activity, last_checked))
+        }))
+    }
+
+    /// Record the activity of an issue as checked now
+    pub(crate) fn set_activity(&self, issue_number: u32, activity: Activity) -> Result<()> {
+        insert_activity(&self.conn(), issue_number, activity, chrono::Utc::now())?;
+        Ok(())
+    }
+
+    /// All cached lists, most recent date first
+    pub fn lists(&self) -> Result<Vec<CachedList>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT date, kind, fetched_at, complete FROM fetched ORDER BY date DESC, kind",
+        )?;
+        let rows = statement
+            .query_map(rusqlite::NO_PARAMS, |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, bool>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        // Rows `verify` complains about are left out
+        Ok(rows
+            .into_iter()
+            .filter_map(|(date, kind, fetched_at, complete)| {
+                Some(CachedList {
+                    date: date.parse().ok()?,
+                    kind: kind.parse().ok()?,
+                    fetched_at: parse_timestamp(&fetched_at),
+                    complete,
+                })
+            })
+            .collect())
+    }
+
+    /// Count what the cache holds
+    pub fn stats(&self) -> Result<CacheStats> {
+        let conn = self.conn();
+        let count = |sql: &str| -> rusqlite::Result<u64> {
+            conn.query_row(sql, rusqlite::NO_PARAMS, |row| row.get::<_, i64>(0))
+                .map(|n| n as u64)
+        };
+        let fresh_after =
+            chrono::Utc::now() - chrono::Duration::hours(crate::triage::ACTIVITY_TTL_HOURS);
+        let fresh_triage = conn.query_row(
+            "SELECT COUNT(*) FROM triage WHERE last_checked >= ?1",
+            params![fresh_after],
+            |row| row.get::<_, i64>(0),
+        )? as u64;
+        let triage = count("SELECT COUNT(*) FROM triage")?;
+        Ok(CacheStats {
+            issues: count("SELECT COUNT(*) FROM issues")?,
+            events: count("SELECT COUNT(*) FROM events")?,
+            comments: count("SELECT COUNT(*) FROM comments")?,
+            complete_lists: count("SELECT COUNT(*) FROM fetched WHERE complete")?,
+            incomplete_lists: count("SELECT COUNT(*) FROM fetched WHERE NOT complete")?,
+            fresh_triage,
+            stale_triage: triage - fresh_triage,
+            size: count("SELECT page_count * page_size FROM pragma_page_count, pragma_page_size")?,
+        })
+    }
+
+    /// Remove the lists of dates before `date` and activity checked before it
+    ///
+    /// Issues created before `date` are removed too unless they are still
+    /// referred to. Returns the number of rows removed.
+    pub fn prune(&self, date: chrono::NaiveDate) -> Result<usize> {
+        let (start, _) = day_bounds(&date);
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        let mut removed = tx.execute("DELETE FROM fetched WHERE date < ?1", params![start])?;
+        tx.execute("DELETE FROM list_issues WHERE date < ?1", params![start])?;
+        removed += tx.execute("DELETE FROM events WHERE created_at < ?1", params![start])?;
+        removed += tx.execute("DELETE FROM comments WHERE created_at < ?1", params![start])?;
+        removed += tx.execute("DELETE FROM triage WHERE last_checked < ?1", params![start])?;
+        removed += tx.execute(
+            "DELETE FROM issues WHERE created_at < ?1
+             AND number NOT IN (SELECT issue FROM events)
+             AND number NOT IN (SELECT issue FROM list_issues)
+             AND number NOT IN (SELECT issue FROM comments)
+             AND number NOT IN (SELECT issue FROM triage)",
+            params![start],
+        )?;
+        tx.execute(
+            "DELETE FROM labels WHERE issue NOT IN (SELECT number FROM issues)",
+            rusqlite::NO_PARAMS,
+        )?;
+        tx.commit()?;
+        debug!("Pruned {} rows older than '{}'", removed, date);
+        compact(&conn)?;
+        Ok(removed)
+    }
+
+    /// Remove the lists of `date`, so they are fetched again
+    ///
+    /// Returns the number of lists removed.
+    pub fn clear_date(&self, date: chrono::NaiveDate) -> Result<usize> {
+        let (start, end) = day_bounds(&date);
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        let removed = tx.execute("DELETE FROM fetched WHERE date = ?1", params![start])?;
+        tx.execute("DELETE FROM list_issues WHERE date = ?1", params![start])?;
+        tx.execute(
+            "DELETE FROM events WHERE created_at >= ?1 AND created_at < ?2",
+            params![start, end],
+        )?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Forget the activity found for an issue, so it is checked again
+    ///
+    /// Returns whether there was any.
+    pub fn clear_activity(&self, issue_number: u32) -> Result<bool> {
+        let removed = self
+            .conn()
+            .execute("DELETE FROM triage WHERE issue = ?1", params![issue_number])?;
+        Ok(removed > 0)
+    }
+
+    /// Remove everything from the cache
+    pub fn clear(&self) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        tx.execute_batch(
+            "DELETE FROM events;
+             DELETE FROM list_issues;
+             DELETE FROM comments;
+             DELETE FROM triage;
+             DELETE FROM fetched;
+             DELETE FROM labels;
+             DELETE FROM issues;",
+        )?;
+        tx.commit()?;
+        compact(&conn)
+    }
+
+    /// Check the cache for corruption and entries this version can't read
+    ///
+    /// Returns a description of each problem found.
+    pub fn verify(&self) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut problems = Vec::new();
+        let mut statement = conn.prepare("PRAGMA integrity_check")?;
+        let integrity = statement
+            .query_map(rusqlite::NO_PARAMS, |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        problems.extend(integrity.into_iter().filter(|line| line != "ok"));
+
+        let mut statement = conn.prepare("SELECT date, kind FROM fetched")?;
+        let lists = statement
+            .query_map(rusqlite::NO_PARAMS, |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (date, kind) in lists {
+            if date.parse::<chrono::NaiveDate>().is_err() {
+                problems.push(format!("list '{}' has an invalid date '{}'", kind, date));
+            }
+            if kind.parse::<CacheType>().is_err() {
+                problems.push(format!(
+                    "list for '{}' has an unknown kind '{}'",
+                    date, kind
+                ));
+            }
+        }
+
+        let orphans = conn.query_row(
+            "SELECT COUNT(*) FROM events WHERE issue NOT IN (SELECT number FROM issues)",
+            rusqlite::NO_PARAMS,
+            |row| row.get::<_, i64>(0),
+        )?;
+        if orphans > 0 {
+            problems.push(format!(
+                "{} events refer to issues missing from the cache",
+                orphans
+            ));
+        }
+        let orphans = conn.query_row(
+            "SELECT COUNT(*) FROM list_issues WHERE issue NOT IN (SELECT number FROM issues)",
+            rusqlite::NO_PARAMS,
+            |row| row.get::<_, i64>(0),
+        )?;
+        if orphans > 0 {
+            problems.push(format!(
+                "{} list entries refer to issues missing from the cache",
+                orphans
+            ));
+        }
+        let unknown = conn.query_row(
+            "SELECT COUNT(*) FROM triage WHERE activity NOT IN ('no-activity-since', 'last-commented')",
+            rusqlite::NO_PARAMS,
+            |row| row.get::<_, i64>(0),
+        )?;
+        if unknown > 0 {
+            problems.push(format!(
+                "{} triage entries have an unknown activity",
+                unknown
+            ));
+        }
+        Ok(problems)
+    }
+
+    /// Write everything in the cache to `out` as a single JSON document
+    ///
+    /// The document has the cached `lists` of each date with their `date`, `kind`,
+    /// `fetched_at`, `complete` and `items`, which are issues or events in the form
+    /// the GitHub API uses. `triage` holds the `activity` last determined for each
+    /// `issue`, the activity's `date` and when the issue was `last_checked`.
+    pub fn export(&self, out: impl std::io::Write) -> Result<()> {
+        #[derive(Serialize)]
+        struct Export {
+            lists: Vec<List>,
+            triage: Vec<Triage>,
+        }
+        #[derive(Serialize)]
+        struct List {
+            date: chrono::NaiveDate,
+            kind: String,
+            fetched_at: Option<chrono::DateTime<chrono::Utc>>,
+            complete: bool,
+            items: serde_json::Value,
+        }
+        #[derive(Serialize)]
+        struct Triage {
+            issue: u32,
+            activity: String,
+            date: chrono::NaiveDate,
+            last_checked: chrono::DateTime<chrono::Utc>,
+        }
+        let mut lists = Vec::new();
+        for list in self.lists()? {
+            let items = match list.kind {
+                CacheType::Issues | CacheType::SearchIssues => {
+                    let issues = self.issues_on(&list.date, list.kind)?;
+                    serde_json::to_value(issues.map(|i| i.items).unwrap_or_default())?
+                }
+                CacheType::Events | CacheType::SearchEvents => {
+                    let events = self.events_on(&list.date, list.kind)?;
+                    serde_json::to_value(events.map(|e| e.items).unwrap_or_default())?
+                }
+            };
+            lists.push(List {
+                date: list.date,
+                kind: list.kind.to_string(),
+                fetched_at: list.fetched_at,
+                complete: list.complete,
+                items,
+            });
+        }
+        let triage = {
+            let conn = self.conn();
+            let mut statement = conn
+                .prepare("SELECT issue, activity, date, last_checked FROM triage ORDER BY issue")?;
+            let rows = statement
+                .query_map(rusqlite::NO_PARAMS, |row| {
+                    Ok(Triage {
+                        issue: row.get(0)?,
+                        activity: row.get(1)?,
+                        date: row.get(2)?,
+                        last_checked: row.get(3)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        serde_json::to_writer(out, &Export { lists, triage })?;
+        Ok(())
+    }
+
+    /// Import the JSON files that versions before the database cached in `dir`
+    ///
+    /// Nothing is imported unless the database was created when the cache was
+    /// opened, so the files are only read once. Lists count as fetched when their
+    /// file was last written. Files that can't be read are skipped.
+    pub fn import_legacy(&self, dir: &Path) -> Result<LegacyImport> {
+        let mut import = LegacyImport::default();
+        if !self.created {
+            return Ok(import);
+        }
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(import),
+            Err(e) => return Err(e.into()),
+        };
+        debug!("Importing legacy cache from '{}'", dir.display());
+        let mut conn = self.conn();
+        let tx = write_transaction(&mut conn)?;
+        for entry in entries {
+            let path = entry?.path();
+            let file_name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let result = if file_name == "triage.json" {
+                import_legacy_triage(&tx, &path).map(|count| import.activities += count)
+            } else if let Some((date, cache_type)) = legacy_list(file_name) {
+                import_legacy_list(&tx, &path, &date, cache_type).map(|()| import.lists += 1)
+            } else {
+                continue;
+            };
+            if let Err(e) = result {
+                debug!("Failed to import '{}': {}", path.display(), e);
+                import.skipped += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(import)
+    }
+}
+
+/// The date and kind of a legacy list from its file name, `<date>-<kind>.json`
+fn legacy_list(file_name: &str) -> Option<(chrono::NaiveDate, CacheType)> {
+    let name = file_name.strip_suffix(".json")?;
+    let date = name.get(..10)?.parse().ok()?;
+    let cache_type = name.get(10..)?.strip_prefix('-')?.parse().ok()?;
+    Some((date, cache_type))
+}
+
+fn import_legacy_list(
+    conn: &Connection,
+    path: &Path,
+    date: &chrono::NaiveDate,
+    cache_type: CacheType,
+) -> Result<()> {
+    let fetched_at = chrono::DateTime::from(std::fs::metadata(path)?.modified()?);
+    let json = std::fs::read(path)?;
+    match cache_type {
+        CacheType::Issues | CacheType::SearchIssues => {
+            let issues = serde_json::from_slice::<Vec<Issue>>(&json)?;
+            insert_issues_on(conn, date, &issues, cache_type, fetched_at)?;
+        }
+        CacheType::Events | CacheType::SearchEvents => {
+            let events = serde_json::from_slice::<Vec<Event>>(&json)?;
+            insert_events_on(conn, date, &events, cache_type, fetched_at)?;
+        }
+    }
+    Ok(())
+}
+
+/// Import the activities in a legacy `triage.json`, returning how many there were
+fn import_legacy_triage(conn: &Connection, path: &Path) -> Result<u64> {
+    #[derive(serde::Deserialize)]
+    struct Line {
+        activity: Activity,
+        last_checked: chrono::DateTime<chrono::Utc>,
+    }
+    let lines: HashMap<u32, Line> = serde_json::from_slice(&std::fs::read(path)?)?;
+    for (issue_number, line) in &lines {
+        insert_activity(conn, *issue_number, line.activity, line.last_checked)?;
+    }
+    Ok(lines.len() as u64)
+}
+
+/// Start a transaction holding the database's write lock
+///
+/// Taking the lock when starting rather than on the first write means a
+/// transaction can't fail halfway because another run wrote in the meantime;
+/// it waits for up to `BUSY_TIMEOUT` to start instead.
+fn write_transaction(conn: &mut Connection) -> rusqlite::Result<Transaction<'_>> {
+    conn.transaction_with_behavior(TransactionBehavior::Immediate)
+}
+
+fn upsert_issue(conn: &Connection, issue: &Issue) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO issues (number, title, comments, is_pull_request, created_at, closed_at, author)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT (number) DO UPDATE SET
+             title = excluded.title,
+             comments = excluded.comments,
+             is_pull_request = excluded.is_pull_request,
+             created_at = excluded.created_at,
+             closed_at = excluded.closed_at,
+             author = COALESCE(excluded.author, issues.author)",
+        params![
+            issue.number,
+            issue.title,
+            issue.comments,
+            issue.is_pull_request(),
+            issue.created_at,
+            issue.closed_at,
+            issue.author.as_ref().map(|a| &a.login)
+        ],
+    )?;
+    conn.execute("DELETE FROM labels WHERE issue = ?1", params![issue.number])?;
+    for label in &issue.labels {
+        conn.execute(
+            "INSERT OR IGNORE INTO labels (issue, name) VALUES (?1, ?2)",
+            params![issue.number, label],
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether the `cache_type` list for `date` has been stored complete, if it has been stored
+fn fetched_state(
+    conn: &Connection,
+    date: &chrono::NaiveDate,
+    cache_type: CacheType,
+) -> rusqlite::Result<Option<bool>> {
+    let fetched = conn
+        .query_row(
+            "SELECT fetched_at, complete FROM fetched WHERE date = ?1 AND kind = ?2",
+            params![date.to_string(), cache_type.to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)),
+        )
+        .optional()?;
+    match fetched {
+        None => {
+            debug!("'{}' for '{}' not in cache", cache_type, date);
+            Ok(None)
+        }
+        Some((fetched_at, false)) => {
+            debug!(
+                "'{}' for '{}' in cache, but may be incomplete (fetched at '{}')",
+                cache_type, date, fetched_at
+            );
+            Ok(Some(false))
+        }
+        Some((_, true)) => Ok(Some(true)),
+    }
+}
+
+/// Replace the list of issues of `cache_type` created on `date` with `issues`
+fn insert_issues_on(
+    conn: &Connection,
+    date: &chrono::NaiveDate,
+    issues: &[Issue],
+    cache_type: CacheType,
+    fetched_at: chrono::DateTime<chrono::Utc>,
+) -> rusqlite::Result<()> {
+    let kind = cache_type.to_string();
+    conn.execute(
+        "DELETE FROM list_issues WHERE date = ?1 AND kind = ?2",
+        params![date.to_string(), kind],
+    )?;
+    for issue in issues {
+        upsert_issue(conn, issue)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO list_issues (date, kind, issue) VALUES (?1, ?2, ?3)",
+            params![date.to_string(), kind, issue.number],
+        )?;
+    }
+    mark_fetched(conn, date, cache_type, fetched_at)
+}
+
+/// Add the events of `cache_type` on `date` to the ones stored before
+fn insert_events_on(
+    conn: &Connection,
+    date: &chrono::NaiveDate,
+    events: &[Event],
+    cache_type: CacheType,
+    fetched_at: chrono::DateTime<chrono::Utc>,
+) -> rusqlite::Result<()> {
+    for event in events {
+        upsert_issue(conn, &event.issue)?;
+        let kind = match event.id {
+            EventId::Closed => "closed",
+            EventId::Reopened => "reopened",
+            EventId::Unknown => "unknown",
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO events (issue, kind, actor, created_at, source)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                event.issue.number,
+                kind,
+                event.actor.as_ref().map(|a| &a.login),
+                event.when,
+                cache_type.to_string()
+            ],
+        )?;
+    }
+    mark_fetched(conn, date, cache_type, fetched_at)
+}
+
+/// Record the activity of an issue as checked at `last_checked`
+fn insert_activity(
+    conn: &Connection,
+    issue_number: u32,
+    activity: Activity,
+    last_checked: chrono::DateTime<chrono::Utc>,
+) -> rusqlite::Result<()> {
+    let (kind, date) = match activity {
+        Activity::NoActivitySince(date) => ("no-activity-since", date),
+        Activity::LastCommented(date) => ("last-commented", date),
+    };
+    conn.execute(
+        "INSERT OR REPLACE INTO triage (issue, activity, date, last_checked)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![issue_number, kind, date, last_checked],
+    )?;
+    Ok(())
+}
+
+/// Whether lists of `date` fetched at `fetched_at` are complete
+pub(crate) fn is_settled(
+    date: &chrono::NaiveDate,
+    fetched_at: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    let settled_at =
+        chrono::DateTime::<chrono::Utc>::from_utc(date.succ().and_hms(0, 0, 0), chrono::Utc)
+            + chrono::Duration::hours(SETTLE_TIME_HOURS);
+    fetched_at >= settled_at
+}
+
+/// Record that the `cache_type` list for `date` was fetched at `fetched_at`
+fn mark_fetched(
+    conn: &Connection,
+    date: &chrono::NaiveDate,
+    cache_type: CacheType,
+    fetched_at: chrono::DateTime<chrono::Utc>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO fetched (date, kind, fetched_at, complete) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            date.to_string(),
+            cache_type.to_string(),
+            fetched_at,
+            is_settled(date, fetched_at)
+        ],
+    )?;
+    Ok(())
+}
+
+/// Upgrade the database to the latest schema version, returning the version it had
+fn migrate(conn: &Connection) -> Result<usize> {
+    let version: usize = conn.query_row("PRAGMA user_version", rusqlite::NO_PARAMS, |row| {
+        row.get::<_, i64>(0)
+    })? as usize;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "the cache has schema version {}, but this version of triage-tracker only knows up to {}",
+            version,
+            MIGRATIONS.len()
+        )
+        .into());
+    }
+    if version == MIGRATIONS.len() {
+        return Ok(version);
+    }
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        debug!("Migrating cache to schema version {}", i + 1);
+        migration(conn)?;
+    }
+    conn.pragma_update(None, "user_version", &(MIGRATIONS.len() as i64))?;
+    Ok(version)
+}
+
+/// Migration 1: create the tables
+fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(INITIAL_SCHEMA)
+}
+
+/// Migration 2: record when lists were fetched and whether they were complete
+///
+/// Lists fetched before are treated as incomplete. Databases created before
+/// schema versions were recorded may have the columns already.
+fn add_fetched_metadata(conn: &Connection) -> rusqlite::Result<()> {
+    let has_metadata = conn.prepare("SELECT complete FROM fetched LIMIT 0").is_ok();
+    if !has_metadata {
+        conn.execute_batch(
+            "ALTER TABLE fetched ADD COLUMN fetched_at TEXT NOT NULL DEFAULT '';
+             ALTER TABLE fetched ADD COLUMN complete INTEGER NOT NULL DEFAULT 0;",
+        )?;
+    }
+    Ok(())
+}
+
+/// Give the space freed by removing rows back to the file system
+fn compact(conn: &Connection) -> Result<()> {
+    conn.execute_batch("VACUUM")?;
+    Ok(())
+}
+
+/// Parse a timestamp as stored by rusqlite, or `None` for a missing one
+fn parse_timestamp(timestamp: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&chrono::Utc))
+}
+
+/// Migration 3: remember the labels of issues
+///
+/// Issues cached before have no labels until they are fetched again.
+fn add_labels(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE labels (
+             issue INTEGER NOT NULL REFERENCES issues (number),
+             name TEXT NOT NULL,
+             UNIQUE (issue, name)
+         );",
+    )
+}
+
+/// Migration 4: remember who opened issues
+///
+/// Issues cached before have no author until they are fetched again.
+fn add_issue_authors(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("ALTER TABLE issues ADD COLUMN author TEXT;")
+}
+
+/// Migration 5: record which issues belong to each date's list
+///
+/// Lists were read back from the issues created on the date before, which also
+/// picked up issues stored for other reasons. Existing lists keep the issues
+/// they were read with, but are marked incomplete so that they are fetched again.
+fn add_list_issues(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE list_issues (
+             date TEXT NOT NULL,
+             kind TEXT NOT NULL,
+             issue INTEGER NOT NULL REFERENCES issues (number),
+             PRIMARY KEY (date, kind, issue)
+         );
+         INSERT INTO list_issues (date, kind, issue)
+             SELECT f.date, f.kind, i.number FROM fetched f
+             JOIN issues i ON substr(i.created_at, 1, 10) = f.date
+             WHERE f.kind = 'issues';
+         UPDATE fetched SET complete = 0 WHERE kind = 'issues';",
+    )
+}
+
+/// Read an issue from the row's `ISSUE_COLUMNS` starting at `start`
+fn issue_from_row(row: &rusqlite::Row<'_>, start: usize) -> rusqlite::Result<Issue> {
+    Ok(Issue {
+        number: row.get(start)?,
+        title: row.get(start + 1)?,
+        comments: row.get(start + 2)?,
+        pull_request: if row.get(start + 3)? {
+            Some(PullRequest {})
+        } else {
+            None
+        },
+        created_at: row.get(start + 4)?,
+        closed_at: row.get(start + 5)?,
+        author: row
+            .get::<_, Option<String>>(start + 6)?
+            .map(|login| Actor { login }),
+        labels: serde_json::from_str(&row.get::<_, String>(start + 7)?).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                start + 7,
+                rusqlite::types::Type::Text,
+                e.into(),
+            )
+        })?,
+    })
+}
+
+/// The bounds of `date` as stored timestamps compare
+///
+/// Timestamps are stored as UTC text starting with the date, so they sort by time.
+fn day_bounds(date: &chrono::NaiveDate) -> (String, String) {
+    (date.to_string(), date.succ().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date() -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd(2021, 6, 7)
+    }
+
+    fn at(hour: u32) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_utc(date().and_hms(hour, 0, 0), chrono::Utc)
+    }
+
+    /// A database as the migrations before `version` left it, with some data in it
+    fn database_at(version: usize) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..version] {
+            migration(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", &(version as i64))
+            .unwrap();
+        conn.execute(
+            "INSERT INTO issues (number, title, comments, is_pull_request, created_at, closed_at)
+             VALUES (1, 'Opened', 2, 0, ?1, NULL), (2, 'Closed', 0, 0, ?2, ?3)",
+            params![at(10), at(1) - chrono::Duration::days(30), at(12)],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO events (issue, kind, actor, created_at, source)
+             VALUES (2, 'closed', 'someone', ?1, 'events')",
+            params![at(12)],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO comments (issue, body, created_at) VALUES (1, 'A comment', ?1)",
+            params![at(11)],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO triage (issue, activity, date, last_checked)
+             VALUES (1, 'last-commented', ?1, ?2)",
+            params![date(), at(13)],
+        )
+        .unwrap();
+        if version >= 2 {
+            // Fetched long after the date settled
+            conn.execute(
+                "INSERT INTO fetched (date, kind, fetched_at, complete)
+                 VALUES (?1, 'issues', ?2, 1), (?1, 'events', ?2, 1)",
+                params![date().to_string(), at(0) + chrono::Duration::days(3)],
+            )
+            .unwrap();
+        } else {
+            conn.execute(
+                "INSERT INTO fetched (date, kind) VALUES (?1, 'issues'), (?1, 'events')",
+                params![date().to_string()],
+            )
+            .unwrap();
+        }
+        if version >= 3 {
+            conn.execute(
+                "INSERT INTO labels (issue, name) VALUES (1, 'bug')",
+                rusqlite::NO_PARAMS,
+            )
+            .unwrap();
+        }
+        if version >= 4 {
+            conn.execute(
+                "UPDATE issues SET author = 'reporter' WHERE number = 1",
+                rusqlite::NO_PARAMS,
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn migrated(conn: Connection, version: usize) -> Cache {
+        assert_eq!(migrate(&conn).unwrap(), version);
+        let migrated: i64 = conn
+            .query_row("PRAGMA user_version", rusqlite::NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(migrated as usize, MIGRATIONS.len());
+        Cache {
+            conn: Mutex::new(conn),
+            created: false,
+        }
+    }
+
+    #[test]
+    fn migrations_keep_data() {
+        for version in 1..MIGRATIONS.len() {
+            let cache = migrated(database_at(version), version);
+            assert!(cache.verify().unwrap().is_empty(), "version {}", version);
+
+            // Only issue lists are fetched again after the last migration
+            let issues = cache
+                .issues_on(&date(), CacheType::Issues)
+                .unwrap()
+                .unwrap();
+            assert_eq!(issues.items.len(), 1, "version {}", version);
+            assert_eq!(issues.items[0].title, "Opened");
+            assert!(!issues.complete);
+            let events = cache
+                .events_on(&date(), CacheType::Events)
+                .unwrap()
+                .unwrap();
+            assert_eq!(events.items.len(), 1, "version {}", version);
+            assert_eq!(events.items[0].issue.number, 2);
+            assert_eq!(events.items[0].actor.as_ref().unwrap().login, "someone");
+            assert_eq!(events.complete, version >= 2, "version {}", version);
+
+            let open = cache.open_issues().unwrap();
+            assert_eq!(open.len(), 1);
+            assert_eq!(open[0].comments, 2);
+            let labels: &[&str] = if version >= 3 { &["bug"] } else { &[] };
+            assert_eq!(open[0].labels, labels, "version {}", version);
+            let author = open[0].author.as_ref().map(|a| a.login.as_str());
+            let expected = if version >= 4 { Some("reporter") } else { None };
+            assert_eq!(author, expected, "version {}", version);
+
+            let (activity, last_checked) = cache.activity(1).unwrap().unwrap();
+            assert!(matches!(activity, Activity::LastCommented(d) if d == date()));
+            assert_eq!(last_checked, at(13));
+            assert_eq!(cache.stats().unwrap().comments, 1);
+        }
+    }
+
+    #[test]
+    fn migrates_databases_from_before_schema_versions() {
+        // Such databases have the first tables, and may have the fetched metadata
+        let conn = database_at(2);
+        conn.pragma_update(None, "user_version", &0).unwrap();
+        let cache = migrated(conn, 0);
+        let lists = cache.lists().unwrap();
+        assert_eq!(lists.len(), 2);
+        assert!(lists.iter().all(|list| list.fetched_at.is_some()));
+
+        let conn = database_at(1);
+        conn.pragma_update(None, "user_version", &0).unwrap();
+        let cache = migrated(conn, 0);
+        let lists = cache.lists().unwrap();
+        assert_eq!(lists.len(), 2);
+        assert!(lists
+            .iter()
+            .all(|list| list.fetched_at.is_none() && !list.complete));
+    }
+
+    #[test]
+    fn newer_schema_versions_are_errors() {
+        let conn = database_at(MIGRATIONS.len());
+        conn.pragma_update(None, "user_version", &(MIGRATIONS.len() as i64 + 1))
+            .unwrap();
+        assert!(migrate(&conn).is_err());
+    }
+
+    const LEGACY_ISSUES: &str = r#"[
+        {
+            "number": 1,
+            "title": "Opened",
+            "comments": 2,
+            "labels": [{"name": "bug"}],
+            "user": {"login": "reporter"},
+            "created_at": "2021-06-07T10:00:00Z",
+            "closed_at": null
+        },
+        {
+            "number": 3,
+            "title": "A pull request",
+            "comments": 0,
+            "pull_request": {},
+            "created_at": "2021-06-07T11:00:00Z",
+            "closed_at": null
+        }
+    ]"#;
+
+    const LEGACY_EVENTS: &str = r#"[
+        {
+            "actor": {"login": "someone"},
+            "event": "closed",
+            "issue": {
+                "number": 2,
+                "title": "Closed",
+                "comments": 0,
+                "created_at": "2021-05-08T01:00:00Z",
+                "closed_at": "2021-06-07T12:00:00Z"
+            },
+            "created_at": "2021-06-07T12:00:00Z"
+        }
+    ]"#;
+
+    const LEGACY_TRIAGE: &str = r#"{
+        "1": {"activity": {"LastCommented": "2021-06-07"}, "last_checked": "2021-06-07T13:00:00Z"}
+    }"#;
+
+    #[test]
+    fn imports_legacy_json() {
+        let dir =
+            std::env::temp_dir().join(format!("triage-tracker-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("2021-06-07-issues.json"), LEGACY_ISSUES).unwrap();
+        std::fs::write(dir.join("2021-06-07-events.json"), LEGACY_EVENTS).unwrap();
+        std::fs::write(dir.join("triage.json"), LEGACY_TRIAGE).unwrap();
+        std::fs::write(dir.join("2021-06-08-issues.json"), "not json").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a list").unwrap();
+
+        let cache = Cache::in_memory().unwrap();
+        let import = cache.import_legacy(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let import = import.unwrap();
+        assert_eq!(import.lists, 2);
+        assert_eq!(import.activities, 1);
+        assert_eq!(import.skipped, 1);
+
+        let issues = cache
+            .issues_on(&date(), CacheType::Issues)
+            .unwrap()
+            .unwrap();
+        let numbers = issues.items.iter().map(|i| i.number).collect::<Vec<_>>();
+        assert_eq!(numbers, [1, 3]);
+        assert_eq!(issues.items[0].labels, ["bug"]);
+        assert_eq!(issues.items[0].author.as_ref().unwrap().login, "reporter");
+        assert!(issues.items[1].is_pull_request());
+        let events = cache
+            .events_on(&date(), CacheType::Events)
+            .unwrap()
+            .unwrap();
+        assert_eq!(events.items.len(), 1);
+        assert_eq!(events.items[0].issue.number, 2);
+        let (activity, last_checked) = cache.activity(1).unwrap().unwrap();
+        assert!(matches!(activity, Activity::LastCommented(d) if d == date()));
+        assert_eq!(last_checked, at(13));
+        assert!(cache.verify().unwrap().is_empty());
+    }
+}
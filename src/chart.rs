@@ -0,0 +1,347 @@
+//! Charts of net closings drawn with plotters
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use triage_tracker::{Error, Issues, Result};
+
+const OPENED: RGBColor = RGBColor(66, 133, 244);
+const CLOSED: RGBColor = RGBColor(219, 68, 55);
+const CUMULATIVE: RGBColor = RGBColor(15, 157, 88);
+
+/// The most dates labelled on the x axis, so that the labels don't overlap
+const MAX_DATE_LABELS: usize = 8;
+
+/// The font all text is drawn in, so that charts don't depend on the fonts installed
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+/// Make `FONT` the `sans-serif` family used by plotters
+///
+/// Text is laid out with it for SVGs too, so this is needed before drawing anything.
+fn register_font() -> Result<()> {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    let mut result = Ok(());
+    REGISTER.call_once(|| {
+        result = plotters::style::register_font("sans-serif", FontStyle::Normal, FONT)
+            .map_err(|_| Error::from("failed to load the chart font"));
+    });
+    result
+}
+
+/// What a chart shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Series {
+    /// The issues opened and closed per day, and the net change
+    Daily,
+    /// The change in open issues since the start of the range
+    Cumulative,
+}
+
+impl std::str::FromStr for Series {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(Self::Daily),
+            "cumulative" => Ok(Self::Cumulative),
+            _ => Err(format!(
+                "unknown series '{}': expected `daily` or `cumulative`",
+                s
+            )),
+        }
+    }
+}
+
+/// How values are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Style {
+    Bar,
+    Line,
+}
+
+impl std::str::FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bar" => Ok(Self::Bar),
+            "line" => Ok(Self::Line),
+            _ => Err(format!("unknown style '{}': expected `bar` or `line`", s)),
+        }
+    }
+}
+
+/// The number of issues opened and closed per day, oldest first
+pub(crate) struct Closings {
+    dates: Vec<chrono::NaiveDate>,
+    /// The opened and closed counts of each date, `None` for dates that aren't cached
+    counts: Vec<Option<(i64, i64)>>,
+}
+
+impl Closings {
+    pub(crate) fn new(days: &[(chrono::NaiveDate, Option<Issues>)]) -> Self {
+        let mut days = days
+            .iter()
+            .map(|(date, issues)| {
+                let counts = issues
+                    .as_ref()
+                    .map(|i| (i.opened().count() as i64, i.closed().count() as i64));
+                (*date, counts)
+            })
+            .collect::<Vec<_>>();
+        days.sort_by_key(|(date, _)| *date);
+        let (dates, counts) = days.into_iter().unzip();
+        Self { dates, counts }
+    }
+
+    /// The change in open issues from the start of the range to the end of each date
+    ///
+    /// Dates that aren't cached count as no change.
+    fn cumulative(&self) -> Vec<i64> {
+        self.counts
+            .iter()
+            .scan(0, |total, counts| {
+                if let Some((opened, closed)) = counts {
+                    *total += opened - closed;
+                }
+                Some(*total)
+            })
+            .collect()
+    }
+
+    /// The number of dates that aren't cached
+    pub(crate) fn missing(&self) -> usize {
+        self.counts.iter().filter(|counts| counts.is_none()).count()
+    }
+
+    /// The known counts with their position on the x axis
+    fn known(&self) -> impl Iterator<Item = (u32, (i64, i64))> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, counts)| counts.map(|counts| (i as u32, counts)))
+    }
+
+    /// The positions of the dates on the x axis
+    ///
+    /// Ranges of integers include their end on plotters' axes.
+    fn x_range(&self) -> std::ops::Range<u32> {
+        0..(self.dates.len() as u32).saturating_sub(1)
+    }
+
+    /// The label for a position on the x axis
+    fn date_label(&self, x: &SegmentValue<u32>) -> String {
+        match x {
+            SegmentValue::CenterOf(i) | SegmentValue::Exact(i) => self
+                .dates
+                .get(*i as usize)
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+            SegmentValue::Last => String::new(),
+        }
+    }
+}
+
+/// Draw `series` in `style`
+pub(crate) fn draw<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    closings: &Closings,
+    series: Series,
+    style: Style,
+    title: &str,
+) -> Result<()> {
+    match series {
+        Series::Daily => draw_daily(area, closings, style, title),
+        Series::Cumulative => draw_cumulative(area, closings, style, title),
+    }
+}
+
+/// Draw the issues opened and closed per day along with the net change
+///
+/// As bars, closed issues point down from zero and the net change is drawn as
+/// a line over them. As lines, all three are drawn above and below zero.
+fn draw_daily<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    closings: &Closings,
+    style: Style,
+    title: &str,
+) -> Result<()> {
+    area.fill(&WHITE).map_err(chart_error)?;
+    let max_opened = closings.known().map(|(_, (opened, _))| opened).max();
+    let max_closed = closings.known().map(|(_, (_, closed))| closed).max();
+    let min_net = closings
+        .known()
+        .map(|(_, (opened, closed))| opened - closed)
+        .min();
+    let (max_opened, max_closed) = (max_opened.unwrap_or(0), max_closed.unwrap_or(0));
+    let y_range = match style {
+        Style::Bar => -(max_closed + 1)..(max_opened + 1),
+        Style::Line => min_net.unwrap_or(0).min(0) - 1..max_opened.max(max_closed) + 1,
+    };
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(closings.x_range().into_segmented(), y_range)
+        .map_err(chart_error)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(MAX_DATE_LABELS)
+        .x_label_formatter(&|x| closings.date_label(x))
+        .y_desc("Issues")
+        .draw()
+        .map_err(chart_error)?;
+    match style {
+        Style::Bar => {
+            chart
+                .draw_series(
+                    Histogram::vertical(&chart)
+                        .style(OPENED.filled())
+                        .margin(2)
+                        .data(closings.known().map(|(i, (opened, _))| (i, opened))),
+                )
+                .map_err(chart_error)?
+                .label("Opened")
+                .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], OPENED.filled()));
+            chart
+                .draw_series(
+                    Histogram::vertical(&chart)
+                        .style(CLOSED.filled())
+                        .margin(2)
+                        .data(closings.known().map(|(i, (_, closed))| (i, -closed))),
+                )
+                .map_err(chart_error)?
+                .label("Closed")
+                .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], CLOSED.filled()));
+        }
+        Style::Line => {
+            chart
+                .draw_series(LineSeries::new(
+                    closings
+                        .known()
+                        .map(|(i, (opened, _))| (SegmentValue::CenterOf(i), opened)),
+                    OPENED.stroke_width(2),
+                ))
+                .map_err(chart_error)?
+                .label("Opened")
+                .legend(|(x, y)| PathElement::new([(x, y), (x + 10, y)], OPENED.stroke_width(2)));
+            chart
+                .draw_series(LineSeries::new(
+                    closings
+                        .known()
+                        .map(|(i, (_, closed))| (SegmentValue::CenterOf(i), closed)),
+                    CLOSED.stroke_width(2),
+                ))
+                .map_err(chart_error)?
+                .label("Closed")
+                .legend(|(x, y)| PathElement::new([(x, y), (x + 10, y)], CLOSED.stroke_width(2)));
+        }
+    }
+    chart
+        .draw_series(LineSeries::new(
+            closings
+                .known()
+                .map(|(i, (opened, closed))| (SegmentValue::CenterOf(i), opened - closed)),
+            BLACK.stroke_width(2),
+        ))
+        .map_err(chart_error)?
+        .label("Net")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 10, y)], BLACK.stroke_width(2)));
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(chart_error)?;
+    Ok(())
+}
+
+/// Draw the change in open issues since the start of the range
+fn draw_cumulative<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    closings: &Closings,
+    style: Style,
+    title: &str,
+) -> Result<()> {
+    area.fill(&WHITE).map_err(chart_error)?;
+    let cumulative = closings.cumulative();
+    let min = cumulative.iter().copied().min().unwrap_or(0).min(0);
+    let max = cumulative.iter().copied().max().unwrap_or(0).max(0);
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(closings.x_range().into_segmented(), (min - 1)..(max + 1))
+        .map_err(chart_error)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(MAX_DATE_LABELS)
+        .x_label_formatter(&|x| closings.date_label(x))
+        .y_desc("Open issues")
+        .draw()
+        .map_err(chart_error)?;
+    match style {
+        Style::Bar => {
+            chart
+                .draw_series(
+                    Histogram::vertical(&chart)
+                        .style(CUMULATIVE.filled())
+                        .margin(2)
+                        .data(
+                            cumulative
+                                .iter()
+                                .enumerate()
+                                .map(|(i, total)| (i as u32, *total)),
+                        ),
+                )
+                .map_err(chart_error)?;
+        }
+        Style::Line => {
+            chart
+                .draw_series(LineSeries::new(
+                    cumulative
+                        .iter()
+                        .enumerate()
+                        .map(|(i, total)| (SegmentValue::CenterOf(i as u32), *total)),
+                    CUMULATIVE.stroke_width(2),
+                ))
+                .map_err(chart_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Draw a chart into an SVG document of `size` pixels
+pub(crate) fn to_svg(
+    size: (u32, u32),
+    draw: impl FnOnce(&DrawingArea<SVGBackend<'_>, Shift>) -> Result<()>,
+) -> Result<String> {
+    register_font()?;
+    let mut svg = String::new();
+    {
+        let area = SVGBackend::with_string(&mut svg, size).into_drawing_area();
+        draw(&area)?;
+        area.present().map_err(chart_error)?;
+    }
+    Ok(svg)
+}
+
+/// Draw a chart into a PNG image of `size` pixels at `path`
+pub(crate) fn to_png(
+    path: &std::path::Path,
+    size: (u32, u32),
+    draw: impl FnOnce(&DrawingArea<BitMapBackend<'_>, Shift>) -> Result<()>,
+) -> Result<()> {
+    register_font()?;
+    let area = BitMapBackend::new(path, size).into_drawing_area();
+    draw(&area)?;
+    area.present().map_err(chart_error)?;
+    Ok(())
+}
+
+fn chart_error<E: std::error::Error + Send + Sync>(error: DrawingAreaErrorKind<E>) -> Error {
+    format!("failed to draw chart: {}", error).into()
+}
@@ -0,0 +1,320 @@
+//! Counting the issues opened and closed on a date
+
+use crate::cache::{self, Cache, CacheType};
+use crate::model::{Actor, Event, EventId, Issue};
+use crate::source::IssueSource;
+use crate::Result;
+
+/// The issues opened and closed on a single date
+pub struct Issues {
+    items: Vec<IssueOrEvent>,
+    complete: bool,
+}
+
+impl Issues {
+    /// Look up the issues opened and closed on `date`, using `cache` where possible
+    ///
+    /// Issues looked up before the date settled may be incomplete, like those
+    /// cached at the time. Dates after today are errors, as nothing can be
+    /// known about them yet.
+    pub async fn for_date(
+        source: &dyn IssueSource,
+        cache: &Cache,
+        date: chrono::NaiveDate,
+    ) -> Result<Self> {
+        // Dates are in UTC, where today may still be yesterday for users east of it
+        let today = chrono::Utc::today().naive_utc();
+        if date > today {
+            return Err(format!(
+                "can't look up {}: it is after today ({} in UTC)",
+                date, today
+            )
+            .into());
+        }
+        let complete = cache::is_settled(&date, chrono::Utc::now());
+        let (events, issues) = tokio::join!(
+            events_for_date(source, cache, date),
+            issues_for_date(source, cache, date)
+        );
+        Ok(Self::new(events?, issues?, complete))
+    }
+
+    /// Look up the issues opened and closed on `date` in `cache` alone
+    ///
+    /// Returns `None` if the date hasn't been cached. Lists cached before the
+    /// date was over are used as they are, which [`Issues::is_complete`] tells.
+    pub fn cached_for_date(
+        source: &dyn IssueSource,
+        cache: &Cache,
+        date: chrono::NaiveDate,
+    ) -> Result<Option<Self>> {
+        let (issues_type, events_type) = cache_types(source);
+        let events = cache.events_on(&date, events_type)?;
+        let issues = cache.issues_on(&date, issues_type)?;
+        Ok(match (events, issues) {
+            (Some(events), Some(issues)) => Some(Self::new(
+                events.items,
+                issues.items,
+                events.complete && issues.complete,
+            )),
+            _ => None,
+        })
+    }
+
+    fn new(events: Vec<Event>, issues: Vec<Issue>, complete: bool) -> Self {
+        let mut items = Vec::with_capacity(events.len() + issues.len());
+        // Sources may return other events than closings and reopenings, which
+        // must not hide the issue being opened on the same date when deduplicating
+        items.extend(
+            events
+                .into_iter()
+                .filter(|e| !e.is_pull_request() && !matches!(e.id, EventId::Unknown))
+                .map(IssueOrEvent::Event),
+        );
+        items.extend(
+            issues
+                .into_iter()
+                .filter(|i| !i.is_pull_request())
+                .map(IssueOrEvent::Issue),
+        );
+        items.sort_by_key(|i| i.issue().number);
+        items.dedup_by(|i1, i2| i1.issue().number == i2.issue().number);
+        Self { items, complete }
+    }
+
+    /// Whether these are all issues opened and closed on the date
+    ///
+    /// Issues looked up before the date settled can be incomplete, as more may
+    /// still be opened or closed, or show up in the forge's lists late.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Issues that were opened or reopened
+    pub fn opened(&self) -> impl Iterator<Item = &Issue> {
+        self.items
+            .iter()
+            .filter(|i| matches!(i.state_change(), StateChange::Opened))
+            .map(|i| i.issue())
+    }
+
+    /// Issues that were closed
+    pub fn closed(&self) -> impl Iterator<Item = &Issue> {
+        self.items
+            .iter()
+            .filter(|i| matches!(i.state_change(), StateChange::Closed))
+            .map(|i| i.issue())
+    }
+
+    /// Issues that were opened or reopened, along with who did so if known
+    pub fn opened_by(&self) -> impl Iterator<Item = (&Issue, Option<&Actor>)> {
+        self.items
+            .iter()
+            .filter(|i| matches!(i.state_change(), StateChange::Opened))
+            .map(|i| (i.issue(), i.actor()))
+    }
+
+    /// Issues that were closed, along with who closed them if known
+    pub fn closed_by(&self) -> impl Iterator<Item = (&Issue, Option<&Actor>)> {
+        self.items
+            .iter()
+            .filter(|i| matches!(i.state_change(), StateChange::Closed))
+            .map(|i| (i.issue(), i.actor()))
+    }
+
+    /// The net change in open issues
+    pub fn diff(&self) -> isize {
+        let opened = self.opened().count() as isize;
+        let closed = self.closed().count() as isize;
+        opened - closed
+    }
+}
+
+async fn events_for_date(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    date: chrono::NaiveDate,
+) -> Result<Vec<Event>> {
+    let (_, cache_type) = cache_types(source);
+    let es = match cache.events_on(&date, cache_type)? {
+        Some(cached) if cached.complete => cached.items,
+        _ => {
+            let events = source.state_changes_on(date).await?;
+            if let Err(e) = cache.store_events_on(&date, &events, cache_type) {
+                eprintln!("Warning: failed to cache events for {}: {}", date, e);
+            }
+            events
+        }
+    };
+    Ok(es)
+}
+
+async fn issues_for_date(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    date: chrono::NaiveDate,
+) -> Result<Vec<Issue>> {
+    let (cache_type, _) = cache_types(source);
+    let es = match cache.issues_on(&date, cache_type)? {
+        Some(cached) if cached.complete => cached.items,
+        _ => {
+            let issues = source.issues_created_on(date).await?;
+            if let Err(e) = cache.store_issues_on(&date, &issues, cache_type) {
+                eprintln!("Warning: failed to cache issues for {}: {}", date, e);
+            }
+            issues
+        }
+    };
+    Ok(es)
+}
+
+/// Where the issues and events of `source` are cached
+///
+/// Sources that miss some state changes, i.e. searching, also list the issues
+/// created on a date differently: in all states rather than GitHub's open ones.
+/// They are cached separately, so that switching sources doesn't mix their
+/// answers.
+fn cache_types(source: &dyn IssueSource) -> (CacheType, CacheType) {
+    if source.sees_all_state_changes() {
+        (CacheType::Issues, CacheType::Events)
+    } else {
+        (CacheType::SearchIssues, CacheType::SearchEvents)
+    }
+}
+
+#[derive(Debug)]
+enum IssueOrEvent {
+    Issue(Issue),
+    Event(Event),
+}
+
+impl IssueOrEvent {
+    fn issue(&self) -> &Issue {
+        match self {
+            Self::Issue(i) => i,
+            Self::Event(e) => &e.issue,
+        }
+    }
+
+    /// Who opened the issue or caused the event
+    fn actor(&self) -> Option<&Actor> {
+        match self {
+            Self::Issue(i) => i.author.as_ref(),
+            Self::Event(e) => e.actor.as_ref(),
+        }
+    }
+
+    fn state_change(&self) -> StateChange {
+        match self {
+            Self::Issue(_) => StateChange::Opened,
+            Self::Event(e) => match &e.id {
+                EventId::Closed => StateChange::Closed,
+                EventId::Reopened => StateChange::Opened,
+                EventId::Unknown => unreachable!("unknown events are dropped by `Issues::new`"),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+enum StateChange {
+    Opened,
+    Closed,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::source::Page;
+    use crate::{Comment, Repo};
+
+    /// A source counting how often it is asked for anything, which it never has
+    struct Unreachable(Repo, AtomicUsize);
+
+    impl Unreachable {
+        fn fetch<T>(&self) -> Result<T> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            Err("fetched from an unreachable source".into())
+        }
+    }
+
+    #[async_trait]
+    impl IssueSource for Unreachable {
+        fn repo(&self) -> &Repo {
+            &self.0
+        }
+
+        fn issue_url(&self, number: u32) -> String {
+            format!("https://example.com/{}", number)
+        }
+
+        async fn issues_created_on(&self, _: chrono::NaiveDate) -> Result<Vec<Issue>> {
+            self.fetch()
+        }
+
+        async fn state_changes_on(&self, _: chrono::NaiveDate) -> Result<Vec<Event>> {
+            self.fetch()
+        }
+
+        async fn open_issues(&self, _: &[String], _: u32) -> Result<Page<Issue>> {
+            self.fetch()
+        }
+
+        async fn comments_since(&self, _: u32, _: chrono::NaiveDate) -> Result<Vec<Comment>> {
+            self.fetch()
+        }
+    }
+
+    fn issue(number: u32) -> Issue {
+        Issue {
+            number,
+            title: format!("Issue {}", number),
+            comments: 0,
+            pull_request: None,
+            labels: Vec::new(),
+            author: None,
+            created_at: chrono::Utc::now(),
+            closed_at: None,
+        }
+    }
+
+    fn event(id: EventId, number: u32) -> Event {
+        Event {
+            actor: None,
+            id,
+            issue: issue(number),
+            when: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn unknown_events_are_ignored() {
+        let events = vec![
+            event(EventId::Unknown, 1),
+            event(EventId::Closed, 2),
+            event(EventId::Unknown, 3),
+        ];
+        let issues = Issues::new(events, vec![issue(1)], true);
+        let opened = issues.opened().map(|i| i.number).collect::<Vec<_>>();
+        let closed = issues.closed().map(|i| i.number).collect::<Vec<_>>();
+        assert_eq!(opened, [1]);
+        assert_eq!(closed, [2]);
+        assert_eq!(issues.diff(), 0);
+    }
+
+    #[tokio::test]
+    async fn future_dates_are_errors() {
+        let source = Unreachable("owner/repo".parse().unwrap(), AtomicUsize::new(0));
+        let cache = Cache::in_memory().unwrap();
+        let tomorrow = chrono::Utc::today().naive_utc().succ();
+        assert!(Issues::for_date(&source, &cache, tomorrow).await.is_err());
+        assert!(Issues::cached_for_date(&source, &cache, tomorrow)
+            .unwrap()
+            .is_none());
+        assert_eq!(source.1.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::Deserialize;
+
+use triage_tracker::Token;
+use triage_tracker::{Repo, Result};
+
+/// Settings read from the optional TOML config file
+///
+/// Every setting can also be given on the command line or through the
+/// environment, which take precedence over the config file.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Config {
+    /// The repository to track as `owner/name`
+    pub(crate) repo: Option<Repo>,
+    /// Where the repository is hosted
+    pub(crate) forge: Option<crate::Forge>,
+    /// Base URL of the forge's API
+    pub(crate) api_url: Option<String>,
+    /// A token for the forge
+    pub(crate) token: Option<Token>,
+    /// Path to a file containing a GitHub token
+    pub(crate) token_file: Option<PathBuf>,
+    /// Whether to wait for rate limits to reset instead of stopping
+    #[serde(default)]
+    pub(crate) wait_on_rate_limit: bool,
+    /// Whether to answer from the cache alone, without sending requests
+    #[serde(default)]
+    pub(crate) offline: bool,
+    /// Timeout in seconds for a single request
+    pub(crate) timeout: Option<u64>,
+    /// Timeout in seconds for establishing a connection
+    pub(crate) connect_timeout: Option<u64>,
+    /// How often to retry requests that failed for transient reasons
+    pub(crate) max_retries: Option<u32>,
+    /// Directory to cache fetched data in
+    pub(crate) cache_dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub(crate) async fn from_file(path: &Path) -> Result<Self> {
+        let contents = match tokio::fs::read_to_string(path).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No config file found at '{}'", path.display());
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        debug!("Reading config from '{}'", path.display());
+        toml::from_str(&contents)
+            .map_err(|e| format!("invalid config file '{}': {}", path.display(), e).into())
+    }
+}
@@ -1,16 +1,69 @@
+//! The errors returned by this crate
+
+/// Any error, boxed so it can be passed between threads
 pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 
+/// A result with this crate's `Error`
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Why a request or command failed
 #[derive(Debug)]
 pub enum Error {
-    RateLimited,
+    /// The forge refused further requests for now
+    RateLimited(RateLimit),
+    /// The configured number of requests have been sent
+    BudgetExhausted(u32),
+    /// Any other failure, like a network, parse or database error
     Other(BoxedError),
 }
 
+/// Details about a rate limit imposed by a forge
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// The name of the forge imposing the limit, e.g. `GitHub`
+    pub forge: &'static str,
+    /// Which of the forge's limits was hit
+    pub kind: RateLimitKind,
+    /// When requests may be sent again, if the forge told us
+    pub retry_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The rate limits a forge imposes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKind {
+    /// The hourly request quota has been used up
+    Primary,
+    /// Too many requests in a short amount of time
+    Secondary,
+}
+
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            RateLimitKind::Primary => "primary",
+            RateLimitKind::Secondary => "secondary",
+        };
+        f.write_fmt(format_args!("{} rate limit", kind))?;
+        if let Some(retry_at) = self.retry_at {
+            f.write_fmt(format_args!(
+                " (resets at {})",
+                retry_at.format("%H:%M:%S UTC")
+            ))?;
+        }
+        Ok(())
+    }
+}
+
 impl From<&str> for Error {
     fn from(error: &str) -> Self {
         Self::Other(error.into())
     }
 }
+impl From<String> for Error {
+    fn from(error: String) -> Self {
+        Self::Other(error.into())
+    }
+}
 impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
         Self::Other(error.into())
@@ -21,6 +74,11 @@ impl From<serde_json::Error> for Error {
         Self::Other(error.into())
     }
 }
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::Other(error.into())
+    }
+}
 impl From<reqwest::Error> for Error {
     fn from(error: reqwest::Error) -> Self {
         Self::Other(error.into())
@@ -35,7 +93,10 @@ impl From<BoxedError> for Error {
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::RateLimited => f.write_str("hit GitHub rate limiting"),
+            Self::RateLimited(limit) => f.write_fmt(format_args!("hit {} {}", limit.forge, limit)),
+            Self::BudgetExhausted(max) => {
+                f.write_fmt(format_args!("used up the budget of {} requests", max))
+            }
             Self::Other(o) => f.write_fmt(format_args!("{}", o)),
         }
     }
@@ -0,0 +1,270 @@
+//! Access to the Gitea and Forgejo API
+//!
+//! The API resembles GitHub's REST API, but issues are listed without a
+//! `pull_request` marker of the same shape and there is no repository-wide
+//! list of issue events. State changes are read from each issue's timeline
+//! instead.
+
+mod source;
+
+use crate::error::{RateLimit, RateLimitKind};
+use crate::http::{self, start_of, ApiClient, ClientOptions, Failure, Links, Token};
+use crate::source::Page;
+use crate::{Actor, Comment, Event, EventId, Issue, PullRequest, Repo, Result};
+use log::debug;
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+/// The API URL of Codeberg, the largest public Forgejo instance
+pub const DEFAULT_API_URL: &str = "https://codeberg.org/api/v1";
+
+/// The most items Gitea returns per page with its default settings
+const MAX_PAGE_SIZE: u8 = 50;
+
+/// Access to the Gitea or Forgejo API for a single repository
+///
+/// `options.api_url` must point at an instance's `/api/v1`, e.g.
+/// [`DEFAULT_API_URL`].
+#[derive(Debug)]
+pub struct Gitea {
+    api: ApiClient,
+    repo: Repo,
+}
+
+impl Gitea {
+    /// A client for `repo`, authenticated with `token` if there is one
+    pub fn new(repo: Repo, token: Option<Token>, options: ClientOptions) -> Result<Self> {
+        let auth = token
+            .map(|token| token.header(AUTHORIZATION, "token "))
+            .transpose()?;
+        let api = ApiClient::new("Gitea", "application/json", auth, options)?;
+        Ok(Self { api, repo })
+    }
+
+    /// The repository this client fetches data for
+    pub fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    /// The link to an issue's web page
+    pub fn issue_url(&self, number: u32) -> String {
+        format!(
+            "{}/{}/issues/{}",
+            http::web_url(&self.api.options.api_url, "/api/v1"),
+            self.repo,
+            number
+        )
+    }
+
+    /// Fetch all issues (but not pull requests) updated since the start of `since`
+    ///
+    /// Issues are returned in any state, along with their labels.
+    pub async fn fetch_issues_updated_since(
+        &self,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<GiteaIssue>> {
+        debug!("Fetching issues updated since '{:?}'", since);
+        let params = [
+            ("state", "all".to_owned()),
+            ("type", "issues".to_owned()),
+            ("since", start_of(since)),
+        ];
+        self.fetch_all("issues", &params).await
+    }
+
+    /// Fetch a page of open issues (but not pull requests) having all of `labels`
+    pub async fn fetch_open_issue_page(
+        &self,
+        labels: &[String],
+        page: u32,
+    ) -> Result<Page<GiteaIssue>> {
+        debug!("Fetching open issue page {}", page);
+        let mut params = vec![("state", "open".to_owned()), ("type", "issues".to_owned())];
+        if !labels.is_empty() {
+            params.push(("labels", labels.join(",")));
+        }
+        self.fetch_page("issues", page, MAX_PAGE_SIZE, &params)
+            .await
+    }
+
+    /// Fetch an issue's timeline from the start of `since`, oldest first
+    pub async fn fetch_timeline(
+        &self,
+        number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<TimelineItem>> {
+        debug!("Fetching timeline for issue {}", number);
+        let params = [("since", start_of(since))];
+        self.fetch_all(&format!("issues/{}/timeline", number), &params)
+            .await
+    }
+
+    /// Fetch the comments on an issue updated since the start of `since`, oldest first
+    pub async fn fetch_comments(
+        &self,
+        number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<Comment>> {
+        debug!("Fetching comments for issue {}", number);
+        let params = [("since", start_of(since))];
+        let url = self.url(&format!("issues/{}/comments", number), &params)?;
+        // Comments are returned all at once rather than paginated
+        let (comments, _) = self.get(url).await?;
+        Ok(comments)
+    }
+
+    /// Fetch every page of a list
+    pub async fn fetch_all<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next_page = Some(1);
+        while let Some(page) = next_page {
+            let results = self.fetch_page(path, page, MAX_PAGE_SIZE, params).await?;
+            next_page = results.next;
+            items.extend(results.items);
+        }
+        Ok(items)
+    }
+
+    /// Fetch a page of a list below the repository, e.g. `issues`
+    pub async fn fetch_page<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        page: u32,
+        per_page: u8,
+        params: &[(&str, String)],
+    ) -> Result<Page<T>> {
+        let mut params = params.to_vec();
+        params.push(("limit", per_page.to_string()));
+        params.push(("page", page.to_string()));
+        let url = self.url(path, &params)?;
+        let (items, headers) = self.get(url).await?;
+        Ok(Page::new(items, Links::from_headers(&headers)))
+    }
+
+    /// The API URL for `path` below the repository with the query parameters `params`
+    fn url(&self, path: &str, params: &[(&str, String)]) -> Result<reqwest::Url> {
+        self.api
+            .url(&format!("repos/{}/{}", self.repo, path), params)
+    }
+
+    /// Send a GET request and decode its response
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        url: reqwest::Url,
+    ) -> Result<(T, HeaderMap)> {
+        self.api
+            .send(Method::GET, url, None, check_rate_limit)
+            .await
+    }
+}
+
+/// An issue or pull request as returned by Gitea
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaIssue {
+    /// The issue's number within the repository
+    pub number: u32,
+    /// The issue's title
+    pub title: String,
+    /// The number of comments
+    pub comments: u32,
+    /// Set for pull requests, whose details differ from GitHub's and aren't needed
+    #[serde(default)]
+    pull_request: Option<serde::de::IgnoredAny>,
+    /// The issue's labels
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// Who opened the issue, if known
+    #[serde(default)]
+    pub user: Option<User>,
+    /// When the issue was opened
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the issue was last closed, if it is closed
+    pub closed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl GiteaIssue {
+    /// Whether this is a pull request rather than an issue
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+}
+
+impl From<GiteaIssue> for Issue {
+    fn from(issue: GiteaIssue) -> Self {
+        Self {
+            number: issue.number,
+            title: issue.title,
+            comments: issue.comments,
+            pull_request: issue.pull_request.map(|_| PullRequest {}),
+            labels: issue.labels.into_iter().map(|l| l.name).collect(),
+            author: issue.user.map(|u| Actor { login: u.login }),
+            created_at: issue.created_at,
+            closed_at: issue.closed_at,
+        }
+    }
+}
+
+/// A label on an issue
+#[derive(Debug, Clone, Deserialize)]
+pub struct Label {
+    /// The label's name
+    pub name: String,
+}
+
+/// Something that happened to an issue, like a comment or it being closed
+#[derive(Debug, Deserialize)]
+pub struct TimelineItem {
+    /// The kind of item, e.g. `comment`, `close` or `reopen`
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// Who caused the item, if known
+    pub user: Option<User>,
+    /// When the item happened
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TimelineItem {
+    /// Convert into an `Event` for `issue`
+    ///
+    /// Items other than closings and reopenings become `EventId::Unknown`.
+    pub fn into_event(self, issue: Issue) -> Event {
+        Event {
+            actor: self.user.map(|u| Actor { login: u.login }),
+            id: match self.typ.as_str() {
+                "close" => EventId::Closed,
+                "reopen" => EventId::Reopened,
+                _ => EventId::Unknown,
+            },
+            issue,
+            when: self.created_at,
+        }
+    }
+}
+
+/// A Gitea user
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    /// The user's name
+    pub login: String,
+}
+
+/// Check a response for rate limiting
+///
+/// Gitea has no rate limits of its own, but is often run behind a proxy that does.
+async fn check_rate_limit(
+    response: reqwest::Response,
+) -> std::result::Result<reqwest::Response, Failure> {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+    Err(Failure::RateLimited(RateLimit {
+        forge: "Gitea",
+        kind: RateLimitKind::Primary,
+        retry_at: http::retry_after(response.headers()),
+    }))
+}
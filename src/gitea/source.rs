@@ -0,0 +1,82 @@
+//! Gitea and Forgejo as an `IssueSource`
+
+use async_trait::async_trait;
+
+use super::*;
+use crate::source::IssueSource;
+
+/// Looks up issues through the repository's issues updated since a date
+///
+/// Gitea can neither filter issues by creation date nor list state changes
+/// across a repository, so both are found among the issues updated since the
+/// date. This gets more expensive the further back the date is.
+#[async_trait]
+impl IssueSource for Gitea {
+    fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    fn issue_url(&self, number: u32) -> String {
+        Gitea::issue_url(self, number)
+    }
+
+    async fn issues_created_on(&self, date: chrono::NaiveDate) -> Result<Vec<Issue>> {
+        Ok(self
+            .fetch_issues_updated_since(date)
+            .await?
+            .into_iter()
+            .map(Issue::from)
+            .filter(|i| i.created_at.date().naive_utc() == date)
+            .collect())
+    }
+
+    async fn state_changes_on(&self, date: chrono::NaiveDate) -> Result<Vec<Event>> {
+        let issues = self.fetch_issues_updated_since(date).await?;
+        debug!(
+            "Checking timelines of {} issues updated since '{:?}'",
+            issues.len(),
+            date
+        );
+        let mut events = Vec::new();
+        for issue in issues {
+            let timeline = self.fetch_timeline(issue.number, date).await?;
+            events.extend(
+                timeline
+                    .into_iter()
+                    .filter(|item| item.created_at.date().naive_utc() == date)
+                    .map(|item| item.into_event(issue.clone().into()))
+                    .filter(|e| !matches!(e.id, EventId::Unknown)),
+            );
+        }
+        Ok(events)
+    }
+
+    async fn open_issues(&self, labels: &[String], page: u32) -> Result<Page<Issue>> {
+        let page = self.fetch_open_issue_page(labels, page).await?;
+        // Labels are checked again in case the server matched issues having any of them
+        Ok(Page {
+            items: page
+                .items
+                .into_iter()
+                .map(Issue::from)
+                .filter(|i| i.has_labels(labels))
+                .collect(),
+            next: page.next,
+            last: page.last,
+        })
+    }
+
+    async fn comments_since(
+        &self,
+        issue_number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<Comment>> {
+        // `since` also matches older comments that were edited later
+        Ok(self
+            .fetch_comments(issue_number, since)
+            .await?
+            .into_iter()
+            .filter(|c| c.created_at.date().naive_utc() >= since)
+            .collect())
+    }
+}
@@ -1,52 +1,296 @@
-use crate::*;
+//! Access to the GitHub REST and GraphQL APIs
+
+mod graphql;
+mod source;
+
+pub use graphql::{TriageIssue, TriagePage};
+pub use source::Search;
+
+use crate::error::{RateLimit, RateLimitKind};
+use crate::http::{
+    self, header, ApiClient, ClientOptions, Failure, Links, Token, SECONDARY_RATE_LIMIT_WAIT,
+};
+use crate::source::Page;
+use crate::{Comment, Error, Event, Issue, Repo, Result};
 use log::debug;
-use reqwest::Client;
-
-pub(crate) async fn fetch_event_page(page: u32, per_page: u8) -> Result<Vec<Event>> {
-    debug!("Fetching event page {}", page);
-    fetch_page(
-        "issues/events",
-        page,
-        per_page,
-        &[],
-        SortedBy::Created,
-        Direction::NewestFirst,
-    )
-    .await
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+/// Access to the GitHub API for a single repository
+///
+/// The underlying HTTP client is shared between all requests so that
+/// connections are pooled.
+#[derive(Debug)]
+pub struct GitHub {
+    api: ApiClient,
+    repo: Repo,
+}
+
+impl GitHub {
+    /// A client for `repo`, authenticated with `token` if there is one
+    ///
+    /// Fails if `options.api_url` isn't a valid URL or the HTTP client can't be built.
+    pub fn new(repo: Repo, token: Option<Token>, options: ClientOptions) -> Result<Self> {
+        let auth = token
+            .map(|token| token.header(AUTHORIZATION, "token "))
+            .transpose()?;
+        let api = ApiClient::new("GitHub", "application/vnd.github.v3+json", auth, options)?;
+        Ok(Self { api, repo })
+    }
+
+    /// The repository this client fetches data for
+    pub fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    /// The link to an issue's web page
+    pub fn issue_url(&self, number: u32) -> String {
+        format!(
+            "{}/{}/issues/{}",
+            html_url(&self.api.options.api_url),
+            self.repo,
+            number
+        )
+    }
+
+    /// Fetch a page of the repository's issue events, newest first
+    pub async fn fetch_event_page(&self, page: u32, per_page: u8) -> Result<Page<Event>> {
+        debug!("Fetching event page {}", page);
+        self.fetch_page(
+            "issues/events",
+            page,
+            per_page,
+            &[],
+            SortedBy::Created,
+            Direction::NewestFirst,
+        )
+        .await
+    }
+
+    /// Fetch a page of the repository's open issues and pull requests having all of `labels`
+    pub async fn fetch_issue_page(
+        &self,
+        page: u32,
+        per_page: u8,
+        labels: &[String],
+        sort_by: SortedBy,
+        direction: Direction,
+    ) -> Result<Page<Issue>> {
+        debug!("Fetching issue page {}", page);
+        self.fetch_page("issues", page, per_page, labels, sort_by, direction)
+            .await
+    }
+
+    /// Fetch a page of the comments on an issue, oldest first
+    ///
+    /// With `since`, only comments updated since the start of that date are
+    /// returned, which includes older comments edited since.
+    pub async fn fetch_comment_page(
+        &self,
+        issue_number: u32,
+        page: u32,
+        per_page: u8,
+        since: Option<chrono::NaiveDate>,
+    ) -> Result<Vec<Comment>> {
+        debug!("Fetching comments for issue {} page {}", issue_number, page);
+        let mut params = vec![
+            ("per_page", per_page.to_string()),
+            ("page", page.to_string()),
+        ];
+        if let Some(since) = since {
+            params.push(("since", http::start_of(since)))
+        }
+        self.fetch(&format!("issues/{}/comments", issue_number), &params)
+            .await
+    }
+
+    /// Search the repository's issues (but not pull requests)
+    ///
+    /// `query` uses GitHub's search syntax, e.g. `closed:2021-06-07`.
+    pub async fn search_issue_page(
+        &self,
+        query: &str,
+        page: u32,
+        per_page: u8,
+    ) -> Result<Page<Issue>> {
+        assert!(per_page <= 100);
+        debug!("Searching issues for '{}' page {}", query, page);
+        let params = [
+            ("q", format!("repo:{} is:issue {}", self.repo, query)),
+            ("per_page", per_page.to_string()),
+            ("page", page.to_string()),
+        ];
+        let url = self.url("search/issues", &params)?;
+        let (results, links): (SearchResults<Issue>, _) = self.get(url).await?;
+        if results.incomplete_results {
+            eprintln!(
+                "Warning: GitHub returned incomplete search results for '{}'",
+                query
+            );
+        }
+        Ok(Page::new(results.items, links))
+    }
+
+    /// Fetch a page of a list below the repository, e.g. `issues`, sorted by `sorted_by`
+    ///
+    /// `per_page` must be at most 100. Only items having all of `labels` are listed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fetch_page<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        page: u32,
+        per_page: u8,
+        labels: &[String],
+        sorted_by: SortedBy,
+        direction: Direction,
+    ) -> Result<Page<T>> {
+        assert!(per_page <= 100);
+
+        let mut params = vec![
+            ("per_page", per_page.to_string()),
+            ("page", page.to_string()),
+            ("sort", sorted_by.to_string()),
+            ("direction", direction.to_string()),
+        ];
+        if !labels.is_empty() {
+            params.push(("labels", labels.join(",")))
+        }
+        // "{api_url}/repos/{owner}/{name}/{}?per_page={}&page={}&sort={}&direction={}{}",
+        // path, per_page, page, sorted_by, direction,labels
+        self.fetch_paged(path, &params).await
+    }
+
+    /// Fetch a list below the repository with the query parameters `params`
+    ///
+    /// Only the page selected by `params` is fetched, without its pagination links.
+    pub async fn fetch<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<Vec<T>> {
+        Ok(self.fetch_paged(path, params).await?.items)
+    }
+
+    /// Fetch a list of items along with the pagination links GitHub sent for it
+    pub async fn fetch_paged<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<Page<T>> {
+        let url = self.url(&format!("repos/{}/{}", self.repo, path), params)?;
+        let (items, links) = self.get(url).await?;
+        Ok(Page::new(items, links))
+    }
+
+    /// The API URL for `path` with the query parameters `params`
+    fn url(&self, path: &str, params: &[(&str, String)]) -> Result<reqwest::Url> {
+        self.api.url(path, params)
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: reqwest::Url) -> Result<(T, Links)> {
+        let (decoded, headers) = self
+            .api
+            .send(Method::GET, url, None, check_rate_limit)
+            .await?;
+        Ok((decoded, Links::from_headers(&headers)))
+    }
+
+    /// Make a single attempt at a request and decode its response
+    async fn try_send<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        url: reqwest::Url,
+        body: Option<&serde_json::Value>,
+    ) -> std::result::Result<(T, Links), Failure> {
+        let (decoded, headers) = self
+            .api
+            .try_send(method, url, body, check_rate_limit)
+            .await?;
+        Ok((decoded, Links::from_headers(&headers)))
+    }
+}
+
+/// The response of the search API
+#[derive(Debug, Deserialize)]
+struct SearchResults<T> {
+    incomplete_results: bool,
+    items: Vec<T>,
 }
 
-pub(crate) async fn fetch_issue_page(
-    page: u32,
-    per_page: u8,
-    labels: &[String],
-    sort_by: SortedBy,
-    direction: Direction,
-) -> Result<Vec<Issue>> {
-    debug!("Fetching issue page {}", page);
-    fetch_page("issues", page, per_page, labels, sort_by, direction).await
+/// The API URL of github.com
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// The base URL of the web interface belonging to an API URL
+///
+/// `https://api.github.com` maps to `https://github.com`, and GitHub Enterprise's
+/// `https://<host>/api/v3` maps to `https://<host>`. Any other URL (e.g. a local
+/// mock server) is used as is.
+fn html_url(api_url: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    if let Some(base) = api_url.strip_suffix("/api/v3") {
+        return base.to_owned();
+    }
+    match api_url.split_once("://api.") {
+        Some((scheme, host)) => format!("{}://{}", scheme, host),
+        None => api_url.to_owned(),
+    }
 }
 
-pub(crate) async fn fetch_comment_page(
-    issue_number: u32,
-    page: u32,
-    per_page: u8,
-    since: Option<chrono::NaiveDate>,
-) -> Result<Vec<Comment>> {
-    debug!("Fetching comments for issue {} page {}", issue_number, page);
-    let mut params = vec![
-        ("per_page", per_page.to_string()),
-        ("page", page.to_string()),
-    ];
-    if let Some(since) = since {
-        let since = chrono::NaiveDateTime::new(since, chrono::NaiveTime::from_hms(0, 0, 0));
-        let since = chrono::DateTime::<chrono::Utc>::from_utc(since, chrono::Utc);
-        params.push(("since", since.format("%Y-%m-%dT%H:%M:%SZ").to_string()))
-    }
-    fetch(&format!("issues/{}/comments", issue_number), &params).await
+/// Check a response for rate limiting, telling it apart from permission errors
+///
+/// Both primary and secondary rate limits may be reported with either a 403 or
+/// a 429 status. A 403 that is not caused by a rate limit is a genuine permission
+/// error and is returned as such.
+async fn check_rate_limit(
+    response: reqwest::Response,
+) -> std::result::Result<reqwest::Response, Failure> {
+    let status = response.status();
+    let headers = response.headers();
+    if let Some(remaining) = header::<u32>(headers, "x-ratelimit-remaining") {
+        debug!("{} requests remaining before rate limiting", remaining);
+    }
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+
+    let now = chrono::Utc::now();
+    let retry_after = http::retry_after(headers);
+    if header::<u32>(headers, "x-ratelimit-remaining") == Some(0) {
+        let reset = http::timestamp(headers, "x-ratelimit-reset");
+        return Err(Failure::RateLimited(RateLimit {
+            forge: "GitHub",
+            kind: RateLimitKind::Primary,
+            retry_at: retry_after.or(reset),
+        }));
+    }
+    if retry_after.is_some() {
+        return Err(Failure::RateLimited(RateLimit {
+            forge: "GitHub",
+            kind: RateLimitKind::Secondary,
+            retry_at: retry_after,
+        }));
+    }
+
+    let url = response.url().clone();
+    let body = response.text().await.unwrap_or_default();
+    if status == StatusCode::TOO_MANY_REQUESTS || body.to_lowercase().contains("rate limit") {
+        return Err(Failure::RateLimited(RateLimit {
+            forge: "GitHub",
+            kind: RateLimitKind::Secondary,
+            retry_at: Some(now + chrono::Duration::seconds(SECONDARY_RATE_LIMIT_WAIT)),
+        }));
+    }
+    Err(Failure::Fatal(
+        format!("GitHub denied access to '{}': {}", url, body.trim()).into(),
+    ))
 }
 
-pub(crate) enum Direction {
+/// The order of a list
+pub enum Direction {
+    /// Most recent (or largest) first
     NewestFirst,
+    /// Least recent (or smallest) first
     OldestFirst,
 }
 
@@ -60,10 +304,13 @@ impl std::fmt::Display for Direction {
     }
 }
 
-#[allow(dead_code)]
-pub(crate) enum SortedBy {
+/// What a list of issues or events is sorted by
+pub enum SortedBy {
+    /// When the item was created
     Created,
+    /// When the item was last updated
     Updated,
+    /// The number of comments on the issue
     Comments,
 }
 
@@ -77,77 +324,3 @@ impl std::fmt::Display for SortedBy {
         f.write_str(s)
     }
 }
-
-pub(crate) async fn fetch_page<T: serde::de::DeserializeOwned>(
-    path: &str,
-    page: u32,
-    per_page: u8,
-    labels: &[String],
-    sorted_by: SortedBy,
-    direction: Direction,
-) -> Result<Vec<T>> {
-    assert!(per_page <= 100);
-
-    let mut params = vec![
-        ("per_page", per_page.to_string()),
-        ("page", page.to_string()),
-        ("sort", sorted_by.to_string()),
-        ("direction", direction.to_string()),
-    ];
-    if !labels.is_empty() {
-        params.push(("labels", labels.join(",")))
-    }
-    // "https://api.github.com/repos/rust-lang/rust/{}?per_page={}&page={}&sort={}&direction={}{}",
-    // path, per_page, page, sorted_by, direction,labels
-    fetch(path, &params).await
-}
-
-const MAX_RETRIES: usize = 3;
-
-pub(crate) async fn fetch<T: serde::de::DeserializeOwned>(
-    path: &str,
-    params: &[(&str, String)],
-) -> Result<Vec<T>> {
-    let params = params
-        .iter()
-        .map(|(k, v)| format!("{}={}", k, v))
-        .collect::<Vec<_>>()
-        .join("&");
-    let mut num_retries = 0;
-    let response = loop {
-        let sent = Client::new()
-            .get(format!(
-                "https://api.github.com/repos/rust-lang/rust/{}?{}",
-                path, params
-            ))
-            .header("Accept", " application/vnd.github.v3+json")
-            .header("User-Agent", "rust-triage-tracker")
-            .send()
-            .await;
-        match sent {
-            Ok(r) => break r,
-            Err(e) => {
-                num_retries += 1;
-                if num_retries > MAX_RETRIES {
-                    return Err(e.into());
-                }
-                debug!("Error sending request: {}\nRetrying...", e);
-
-                // TODO: maybe exponential back off is better?
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-            }
-        }
-    };
-
-    Ok(response
-        .error_for_status()
-        .map_err(|e| -> Error {
-            if let Some(reqwest::StatusCode::FORBIDDEN) = e.status() {
-                Error::RateLimited
-            } else {
-                e.into()
-            }
-        })?
-        .json()
-        .await?)
-}
@@ -0,0 +1,240 @@
+//! Fetching triage data through GitHub's GraphQL API
+//!
+//! A single GraphQL query returns a page of open issues together with their labels
+//! and most recent comment, where the REST API needs one extra request per issue.
+
+use super::*;
+
+const TRIAGE_QUERY: &str = r#"
+query($owner: String!, $name: String!, $labels: [String!], $cursor: String) {
+  repository(owner: $owner, name: $name) {
+    issues(
+      first: 100
+      after: $cursor
+      states: OPEN
+      labels: $labels
+      orderBy: { field: COMMENTS, direction: ASC }
+    ) {
+      pageInfo {
+        hasNextPage
+        endCursor
+      }
+      nodes {
+        number
+        title
+        author {
+          login
+        }
+        createdAt
+        comments(last: 1) {
+          totalCount
+          nodes {
+            createdAt
+          }
+        }
+        labels(first: 50) {
+          nodes {
+            name
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// An open issue along with when it was last commented on
+#[derive(Debug)]
+pub struct TriageIssue {
+    /// The issue, with its labels
+    pub issue: Issue,
+    /// When the most recent comment was made, if there are any comments
+    pub last_commented: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A page of open issues returned by `GitHub::fetch_triage_page`
+#[derive(Debug)]
+pub struct TriagePage {
+    /// The issues on the page having all of the requested labels
+    pub issues: Vec<TriageIssue>,
+    /// The cursor to pass to get the next page, if there is one
+    pub next: Option<String>,
+}
+
+impl GitHub {
+    /// Fetch a page of open issues having all of `labels`
+    ///
+    /// Issues are sorted by the number of comments, fewest first, like
+    /// `fetch_issue_page` with `SortedBy::Comments` and `Direction::OldestFirst`.
+    pub async fn fetch_triage_page(
+        &self,
+        labels: &[String],
+        cursor: Option<&str>,
+    ) -> Result<TriagePage> {
+        if !self.api.is_authenticated() {
+            return Err("the GraphQL API can only be used with a GitHub token".into());
+        }
+        debug!("Fetching triage page after cursor {:?}", cursor);
+        let body = serde_json::json!({
+            "query": TRIAGE_QUERY,
+            "variables": {
+                "owner": self.repo.owner,
+                "name": self.repo.name,
+                // GraphQL matches issues with *any* of the labels, so the results
+                // are filtered below to match the REST API's *all* semantics
+                "labels": if labels.is_empty() { None } else { Some(labels) },
+                "cursor": cursor,
+            },
+        });
+        let url = reqwest::Url::parse(&graphql_url(&self.api.options.api_url))
+            .map_err(|e| format!("invalid GraphQL URL: {}", e))?;
+        let response = self
+            .api
+            .send_with_retries(&url, || async {
+                let (response, _): (Response, _) = self
+                    .try_send(Method::POST, url.clone(), Some(&body))
+                    .await?;
+                // GraphQL reports running out of its quota in the body of a successful
+                // response, without saying when the quota resets
+                if response
+                    .errors
+                    .iter()
+                    .any(|e| e.typ.as_deref() == Some("RATE_LIMITED"))
+                {
+                    return Err(Failure::RateLimited(RateLimit {
+                        forge: "GitHub",
+                        kind: RateLimitKind::Primary,
+                        retry_at: None,
+                    }));
+                }
+                Ok(response)
+            })
+            .await?;
+        if let Some(error) = response.errors.first() {
+            return Err(format!("GraphQL query failed: {}", error.message).into());
+        }
+        let issues = response
+            .data
+            .and_then(|d| d.repository)
+            .ok_or_else(|| Error::from(format!("repository '{}' not found", self.repo)))?
+            .issues;
+
+        let next = if issues.page_info.has_next_page {
+            issues.page_info.end_cursor
+        } else {
+            None
+        };
+        let issues = issues
+            .nodes
+            .into_iter()
+            .filter(|node| {
+                labels
+                    .iter()
+                    .all(|l| node.labels.nodes.iter().any(|n| &n.name == l))
+            })
+            .map(|node| TriageIssue {
+                last_commented: node.comments.nodes.last().map(|c| c.created_at),
+                issue: Issue {
+                    number: node.number,
+                    title: node.title,
+                    comments: node.comments.total_count,
+                    pull_request: None,
+                    labels: node.labels.nodes.into_iter().map(|l| l.name).collect(),
+                    author: node.author.map(|a| crate::Actor { login: a.login }),
+                    created_at: node.created_at,
+                    closed_at: None,
+                },
+            })
+            .collect();
+        Ok(TriagePage { issues, next })
+    }
+}
+
+/// The GraphQL endpoint belonging to a REST API URL
+///
+/// GitHub Enterprise serves REST at `/api/v3` and GraphQL at `/api/graphql`.
+fn graphql_url(api_url: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    match api_url.strip_suffix("/v3") {
+        Some(base) if base.ends_with("/api") => format!("{}/graphql", base),
+        _ => format!("{}/graphql", api_url),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    data: Option<Data>,
+    #[serde(default)]
+    errors: Vec<QueryError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryError {
+    message: String,
+    #[serde(rename = "type")]
+    typ: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Data {
+    repository: Option<Repository>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    issues: Connection<IssueNode>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    page_info: PageInfo,
+    nodes: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueNode {
+    number: u32,
+    title: String,
+    /// `None` for deleted accounts
+    author: Option<AuthorNode>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    comments: CommentConnection,
+    labels: LabelConnection,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorNode {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentConnection {
+    total_count: u32,
+    nodes: Vec<CommentNode>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentNode {
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelConnection {
+    nodes: Vec<LabelNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelNode {
+    name: String,
+}
@@ -0,0 +1,147 @@
+//! GitHub as an `IssueSource`
+
+use async_trait::async_trait;
+
+use super::*;
+use crate::source::{fetch_for_date, IssueSource};
+use crate::EventId;
+
+/// Looks up issues through the repository's issue and event lists
+///
+/// Those lists are sorted newest first, so old dates are found by binary search.
+#[async_trait]
+impl IssueSource for GitHub {
+    fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    fn issue_url(&self, number: u32) -> String {
+        GitHub::issue_url(self, number)
+    }
+
+    async fn issues_created_on(&self, date: chrono::NaiveDate) -> Result<Vec<Issue>> {
+        fetch_for_date(date, |page| {
+            self.fetch_issue_page(page, 100, &[], SortedBy::Created, Direction::NewestFirst)
+        })
+        .await
+    }
+
+    async fn state_changes_on(&self, date: chrono::NaiveDate) -> Result<Vec<Event>> {
+        fetch_for_date(date, |page| self.fetch_event_page(page, 100)).await
+    }
+
+    async fn open_issues(&self, labels: &[String], page: u32) -> Result<Page<Issue>> {
+        self.fetch_issue_page(
+            page,
+            100,
+            labels,
+            SortedBy::Comments,
+            Direction::OldestFirst,
+        )
+        .await
+    }
+
+    async fn comments_since(
+        &self,
+        issue_number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<Comment>> {
+        let mut comments = Vec::new();
+        let mut page = 1;
+        loop {
+            let items = self
+                .fetch_comment_page(issue_number, page, 100, Some(since))
+                .await?;
+            let done = items.len() < 100;
+            // `since` also matches older comments that were edited later
+            comments.extend(
+                items
+                    .into_iter()
+                    .filter(|c| c.created_at.date().naive_utc() >= since),
+            );
+            if done {
+                return Ok(comments);
+            }
+            page += 1;
+        }
+    }
+
+    fn as_github(&self) -> Option<&GitHub> {
+        Some(self)
+    }
+}
+
+/// Looks up issues through GitHub's search API
+///
+/// This needs far fewer requests for old dates, but only sees the most
+/// recent closing of an issue and ignores reopenings.
+#[derive(Debug, Clone, Copy)]
+pub struct Search<'a>(pub &'a GitHub);
+
+#[async_trait]
+impl IssueSource for Search<'_> {
+    fn repo(&self) -> &Repo {
+        &self.0.repo
+    }
+
+    fn issue_url(&self, number: u32) -> String {
+        self.0.issue_url(number)
+    }
+
+    async fn issues_created_on(&self, date: chrono::NaiveDate) -> Result<Vec<Issue>> {
+        search_for_date(self.0, "created", date).await
+    }
+
+    /// Closing events synthesized from the issues closed on `date`
+    async fn state_changes_on(&self, date: chrono::NaiveDate) -> Result<Vec<Event>> {
+        Ok(search_for_date(self.0, "closed", date)
+            .await?
+            .into_iter()
+            .filter_map(|issue| {
+                Some(Event {
+                    actor: None,
+                    id: EventId::Closed,
+                    when: issue.closed_at?,
+                    issue,
+                })
+            })
+            .collect())
+    }
+
+    fn sees_all_state_changes(&self) -> bool {
+        false
+    }
+
+    async fn open_issues(&self, labels: &[String], page: u32) -> Result<Page<Issue>> {
+        self.0.open_issues(labels, page).await
+    }
+
+    async fn comments_since(
+        &self,
+        issue_number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<Comment>> {
+        self.0.comments_since(issue_number, since).await
+    }
+
+    fn as_github(&self) -> Option<&GitHub> {
+        Some(self.0)
+    }
+}
+
+/// Find all issues whose `qualifier` (e.g. `created` or `closed`) date is `date`
+async fn search_for_date(
+    github: &GitHub,
+    qualifier: &str,
+    date: chrono::NaiveDate,
+) -> Result<Vec<Issue>> {
+    let query = format!("{}:{}", qualifier, date.format("%Y-%m-%d"));
+    let mut issues = Vec::new();
+    let mut next_page = Some(1);
+    while let Some(page) = next_page {
+        let results = github.search_issue_page(&query, page, 100).await?;
+        next_page = results.next;
+        issues.extend(results.items);
+    }
+    Ok(issues)
+}
@@ -0,0 +1,252 @@
+//! Access to the GitLab API
+//!
+//! GitLab's issues, resource state events and notes are converted into the
+//! same `Issue`, `Event` and `Comment` types used for GitHub, with an issue's
+//! project-local `iid` as its number.
+
+mod source;
+
+use crate::error::{RateLimit, RateLimitKind};
+use crate::http::{self, header, start_of, ApiClient, ClientOptions, Failure, Token};
+use crate::source::Page;
+use crate::{Actor, Comment, Event, EventId, Issue, Repo, Result};
+use log::debug;
+use reqwest::header::{HeaderMap, HeaderName};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+/// The API URL of gitlab.com
+pub const DEFAULT_API_URL: &str = "https://gitlab.com/api/v4";
+
+/// Access to the GitLab API for a single project
+///
+/// `options.api_url` must point at a GitLab instance's `/api/v4`, e.g.
+/// [`DEFAULT_API_URL`].
+#[derive(Debug)]
+pub struct GitLab {
+    api: ApiClient,
+    project: Repo,
+}
+
+impl GitLab {
+    /// A client for `project`, authenticated with `token` if there is one
+    pub fn new(project: Repo, token: Option<Token>, options: ClientOptions) -> Result<Self> {
+        let auth = token
+            .map(|token| token.header(HeaderName::from_static("private-token"), ""))
+            .transpose()?;
+        let api = ApiClient::new("GitLab", "application/json", auth, options)?;
+        Ok(Self { api, project })
+    }
+
+    /// The project this client fetches data for
+    pub fn project(&self) -> &Repo {
+        &self.project
+    }
+
+    /// The link to an issue's web page
+    pub fn issue_url(&self, iid: u32) -> String {
+        format!(
+            "{}/{}/-/issues/{}",
+            http::web_url(&self.api.options.api_url, "/api/v4"),
+            self.project,
+            iid
+        )
+    }
+
+    /// Fetch a page of the project's issues, in any state unless `params` say otherwise
+    pub async fn fetch_issue_page(
+        &self,
+        page: u32,
+        per_page: u8,
+        params: &[(&str, String)],
+    ) -> Result<Page<Issue>> {
+        debug!("Fetching issue page {}", page);
+        let page: Page<GitLabIssue> = self.fetch_page("issues", page, per_page, params).await?;
+        Ok(Page {
+            items: page.items.into_iter().map(Issue::from).collect(),
+            next: page.next,
+            last: page.last,
+        })
+    }
+
+    /// Fetch all closings and reopenings of an issue, oldest first
+    pub async fn fetch_state_events(&self, iid: u32) -> Result<Vec<StateEvent>> {
+        debug!("Fetching state events for issue {}", iid);
+        self.fetch_all(&format!("issues/{}/resource_state_events", iid), &[])
+            .await
+    }
+
+    /// Fetch all notes on an issue, oldest first
+    ///
+    /// System notes (e.g. "changed the description") are included.
+    pub async fn fetch_notes(&self, iid: u32) -> Result<Vec<Note>> {
+        debug!("Fetching notes for issue {}", iid);
+        let params = [
+            ("order_by", "created_at".to_owned()),
+            ("sort", "asc".to_owned()),
+        ];
+        self.fetch_all(&format!("issues/{}/notes", iid), &params)
+            .await
+    }
+
+    /// Fetch every page of a list
+    pub async fn fetch_all<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next_page = Some(1);
+        while let Some(page) = next_page {
+            let results = self.fetch_page(path, page, 100, params).await?;
+            next_page = results.next;
+            items.extend(results.items);
+        }
+        Ok(items)
+    }
+
+    /// Fetch a page of a list below the project, e.g. `issues`
+    pub async fn fetch_page<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        page: u32,
+        per_page: u8,
+        params: &[(&str, String)],
+    ) -> Result<Page<T>> {
+        assert!(per_page <= 100);
+        let mut params = params.to_vec();
+        params.push(("per_page", per_page.to_string()));
+        params.push(("page", page.to_string()));
+        let url = self.url(path, &params)?;
+        let (items, headers) = self.get(url).await?;
+        Ok(Page {
+            items,
+            next: header(&headers, "x-next-page"),
+            last: header::<u32>(&headers, "x-total-pages").filter(|&last| last > page),
+        })
+    }
+
+    /// The API URL for `path` below the project with the query parameters `params`
+    fn url(&self, path: &str, params: &[(&str, String)]) -> Result<reqwest::Url> {
+        // Projects are addressed by their URL-encoded full path
+        let project = self.project.to_string().replace('/', "%2F");
+        self.api
+            .url(&format!("projects/{}/{}", project, path), params)
+    }
+
+    /// Send a GET request and decode its response
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        url: reqwest::Url,
+    ) -> Result<(T, HeaderMap)> {
+        self.api
+            .send(Method::GET, url, None, check_rate_limit)
+            .await
+    }
+}
+
+/// An issue as returned by GitLab
+#[derive(Debug, Clone, Deserialize)]
+struct GitLabIssue {
+    iid: u32,
+    title: String,
+    user_notes_count: u32,
+    #[serde(default)]
+    labels: Vec<String>,
+    #[serde(default)]
+    author: Option<User>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    closed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<GitLabIssue> for Issue {
+    fn from(issue: GitLabIssue) -> Self {
+        Self {
+            number: issue.iid,
+            title: issue.title,
+            comments: issue.user_notes_count,
+            // Merge requests are never listed among issues
+            pull_request: None,
+            labels: issue.labels,
+            author: issue.author.map(|u| Actor { login: u.username }),
+            created_at: issue.created_at,
+            closed_at: issue.closed_at,
+        }
+    }
+}
+
+/// A change of an issue's state, like it being closed
+#[derive(Debug, Deserialize)]
+pub struct StateEvent {
+    /// Who changed the state, if known
+    pub user: Option<User>,
+    /// When the state changed
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// `closed` or `reopened` for issues
+    pub state: String,
+}
+
+impl StateEvent {
+    /// Convert into an `Event` for `issue`
+    ///
+    /// Other states than `closed` and `reopened` become `EventId::Unknown`.
+    pub fn into_event(self, issue: Issue) -> Event {
+        Event {
+            actor: self.user.map(|u| Actor { login: u.username }),
+            id: match self.state.as_str() {
+                "closed" => EventId::Closed,
+                "reopened" => EventId::Reopened,
+                _ => EventId::Unknown,
+            },
+            issue,
+            when: self.created_at,
+        }
+    }
+}
+
+/// A GitLab user
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    /// The user's name
+    pub username: String,
+}
+
+/// A comment on an issue, or a note GitLab made about a change to it
+#[derive(Debug, Deserialize)]
+pub struct Note {
+    /// The note's Markdown text
+    pub body: String,
+    /// When the note was made
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Set for notes GitLab made itself
+    pub system: bool,
+}
+
+impl From<Note> for Comment {
+    fn from(note: Note) -> Self {
+        Self {
+            body: note.body,
+            created_at: note.created_at,
+        }
+    }
+}
+
+/// Check a response for rate limiting
+async fn check_rate_limit(
+    response: reqwest::Response,
+) -> std::result::Result<reqwest::Response, Failure> {
+    let headers = response.headers();
+    if let Some(remaining) = header::<u32>(headers, "ratelimit-remaining") {
+        debug!("{} requests remaining before rate limiting", remaining);
+    }
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+    let retry_after = http::retry_after(headers);
+    let reset = http::timestamp(headers, "ratelimit-reset");
+    Err(Failure::RateLimited(RateLimit {
+        forge: "GitLab",
+        kind: RateLimitKind::Primary,
+        retry_at: retry_after.or(reset),
+    }))
+}
@@ -0,0 +1,89 @@
+//! GitLab as an `IssueSource`
+
+use async_trait::async_trait;
+
+use super::*;
+use crate::source::IssueSource;
+
+/// Looks up issues through the project's issue list, which GitLab can filter by date
+///
+/// GitLab has no list of state changes across a project, so closings on a date
+/// are found by checking the state events of every issue updated since then.
+/// This gets more expensive the further back the date is.
+#[async_trait]
+impl IssueSource for GitLab {
+    fn repo(&self) -> &Repo {
+        &self.project
+    }
+
+    fn issue_url(&self, number: u32) -> String {
+        GitLab::issue_url(self, number)
+    }
+
+    async fn issues_created_on(&self, date: chrono::NaiveDate) -> Result<Vec<Issue>> {
+        let params = [
+            ("created_after", start_of(date)),
+            ("created_before", start_of(date.succ())),
+        ];
+        Ok(self
+            .fetch_all::<GitLabIssue>("issues", &params)
+            .await?
+            .into_iter()
+            .map(Issue::from)
+            .filter(|i| i.created_at.date().naive_utc() == date)
+            .collect())
+    }
+
+    async fn state_changes_on(&self, date: chrono::NaiveDate) -> Result<Vec<Event>> {
+        // Closing or reopening an issue updates it, so only issues updated
+        // since the start of `date` can have changed state on it
+        let params = [("updated_after", start_of(date))];
+        let issues = self.fetch_all::<GitLabIssue>("issues", &params).await?;
+        debug!(
+            "Checking state events of {} issues updated since '{:?}'",
+            issues.len(),
+            date
+        );
+        let mut events = Vec::new();
+        for issue in issues {
+            let state_events = self.fetch_state_events(issue.iid).await?;
+            events.extend(
+                state_events
+                    .into_iter()
+                    .filter(|e| e.created_at.date().naive_utc() == date)
+                    .map(|e| e.into_event(issue.clone().into()))
+                    .filter(|e| !matches!(e.id, EventId::Unknown)),
+            );
+        }
+        Ok(events)
+    }
+
+    async fn open_issues(&self, labels: &[String], page: u32) -> Result<Page<Issue>> {
+        // GitLab can't sort by the number of comments, which the triage
+        // loop doesn't rely on anyway
+        let mut params = vec![
+            ("state", "opened".to_owned()),
+            ("order_by", "created_at".to_owned()),
+            ("sort", "asc".to_owned()),
+        ];
+        if !labels.is_empty() {
+            params.push(("labels", labels.join(",")));
+        }
+        self.fetch_issue_page(page, 100, &params).await
+    }
+
+    async fn comments_since(
+        &self,
+        issue_number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<Comment>> {
+        // Notes can't be filtered by date, so all of them are fetched
+        Ok(self
+            .fetch_notes(issue_number)
+            .await?
+            .into_iter()
+            .filter(|n| !n.system && n.created_at.date().naive_utc() >= since)
+            .map(Comment::from)
+            .collect())
+    }
+}
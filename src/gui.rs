@@ -7,7 +7,9 @@ use tui::widgets::{BarChart, Block, Borders};
 use tui::Terminal;
 
 #[allow(dead_code)]
-pub(crate) async fn gui(issues: Vec<(chrono::NaiveDate, crate::Issues)>) -> crate::Result<()> {
+pub(crate) async fn gui(
+    issues: Vec<(chrono::NaiveDate, triage_tracker::Issues)>,
+) -> triage_tracker::Result<()> {
     let data = std::sync::Arc::new(
         issues
             .into_iter()
@@ -34,17 +36,15 @@ pub(crate) async fn gui(issues: Vec<(chrono::NaiveDate, crate::Issues)>) -> crat
     use termion::input::TermRead;
     let tx_clone = tx.clone();
     tokio::spawn(async move {
-        for evt in stdin.keys() {
-            if let Ok(key) = evt {
-                if let Err(_) = tx_clone.send(Event::Key(key)).await {
-                    return;
-                }
+        for key in stdin.keys().flatten() {
+            if tx_clone.send(Event::Key(key)).await.is_err() {
+                return;
             }
         }
     });
     tokio::spawn(async move {
         loop {
-            if let Err(_) = tx.send(Event::Tick).await {
+            if tx.send(Event::Tick).await.is_err() {
                 break;
             }
             tokio::time::sleep(std::time::Duration::from_millis(500)).await;
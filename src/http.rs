@@ -0,0 +1,519 @@
+//! The HTTP client shared by the forges, and the settings for it
+//!
+//! Each forge builds its own URLs and recognizes its own rate limits, while
+//! sending requests, retrying them and decoding their responses works the same
+//! for all of them.
+
+use crate::error::RateLimit;
+use crate::source::Page;
+use crate::{Error, Result};
+use log::debug;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, LINK};
+use reqwest::{Client, Method, StatusCode};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A personal access token for a forge
+///
+/// The `Debug` implementation never prints the token itself.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Token(String);
+
+impl Token {
+    /// A token from its text, `None` if it is empty or whitespace
+    pub fn new(token: &str) -> Option<Self> {
+        let token = token.trim();
+        if token.is_empty() {
+            None
+        } else {
+            Some(Self(token.to_owned()))
+        }
+    }
+
+    /// Read a token from the first line of a file
+    pub async fn from_file(path: &std::path::Path) -> Result<Self> {
+        let contents = tokio::fs::read_to_string(path).await.map_err(|e| {
+            Error::from(format!(
+                "failed to read token file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        contents
+            .lines()
+            .next()
+            .and_then(Self::new)
+            .ok_or_else(|| format!("token file '{}' is empty", path.display()).into())
+    }
+
+    /// The header `name` carrying the token after `prefix`, with a value that is never logged
+    pub(crate) fn header(
+        &self,
+        name: HeaderName,
+        prefix: &str,
+    ) -> Result<(HeaderName, HeaderValue)> {
+        let mut value = HeaderValue::from_str(&format!("{}{}", prefix, self.0))
+            .map_err(|_| Error::from("token contains invalid characters"))?;
+        value.set_sensitive(true);
+        Ok((name, value))
+    }
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Token(<redacted>)")
+    }
+}
+
+/// Settings controlling how requests to a forge are sent
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Base URL of the forge's REST API, e.g. `https://ghe.example.com/api/v3` for
+    /// GitHub Enterprise; github.com's by default
+    pub api_url: String,
+    /// Timeout for a whole request including reading the response body
+    pub timeout: Duration,
+    /// Timeout for establishing a connection
+    pub connect_timeout: Duration,
+    /// How to retry requests that failed for transient reasons
+    pub retry: RetryPolicy,
+    /// Sleep until a rate limit resets instead of returning `Error::RateLimited`
+    pub wait_on_rate_limit: bool,
+    /// How many requests may be sent
+    pub budget: RequestBudget,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            api_url: crate::github::DEFAULT_API_URL.to_owned(),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+            wait_on_rate_limit: false,
+            budget: RequestBudget::default(),
+        }
+    }
+}
+
+/// A limit on the number of requests sent, unlimited by default
+///
+/// Clones share what has been spent, so a budget can be kept around to see how
+/// many requests a client sent.
+#[derive(Debug, Clone, Default)]
+pub struct RequestBudget {
+    max: Option<u32>,
+    sent: Arc<AtomicU32>,
+}
+
+impl RequestBudget {
+    /// A budget of `max` requests
+    pub fn new(max: u32) -> Self {
+        Self {
+            max: Some(max),
+            sent: Arc::default(),
+        }
+    }
+
+    /// The number of requests sent so far
+    pub fn sent(&self) -> u32 {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    /// Account for a request about to be sent, or fail if the budget is used up
+    pub(crate) fn spend(&self) -> Result<()> {
+        let sent = self.sent.fetch_add(1, Ordering::SeqCst);
+        match self.max {
+            Some(max) if sent >= max => {
+                self.sent.fetch_sub(1, Ordering::SeqCst);
+                Err(Error::BudgetExhausted(max))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Exponential backoff with jitter for transient failures
+///
+/// Transient failures are connection errors and timeouts, 5xx responses that
+/// forges use for temporary outages, and response bodies that fail to decode.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// How often a request is retried before its failure is returned
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled for each further retry
+    pub initial_backoff: Duration,
+    /// The longest delay before a retry
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before the given retry (starting at 1)
+    ///
+    /// A random delay between half and all of the exponential backoff is used,
+    /// so that concurrent requests don't retry in lockstep.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        backoff / 2 + backoff.mul_f64(rand::random::<f64>() / 2.0)
+    }
+}
+
+/// How long to wait on a rate limit when the forge does not say
+pub(crate) const SECONDARY_RATE_LIMIT_WAIT: i64 = 60;
+
+/// An HTTP client for one forge's API
+///
+/// The underlying client is shared between all requests so that connections
+/// are pooled.
+#[derive(Debug)]
+pub(crate) struct ApiClient {
+    client: Client,
+    /// The header carrying the token, if there is one
+    auth: Option<(HeaderName, HeaderValue)>,
+    pub(crate) options: ClientOptions,
+}
+
+/// Why a single attempt at a request failed
+pub(crate) enum Failure {
+    RateLimited(RateLimit),
+    /// Failures that may succeed when retried
+    Transient(Error),
+    Fatal(Error),
+}
+
+impl ApiClient {
+    /// A client for `forge`'s API accepting responses of type `accept`
+    ///
+    /// Fails if `options.api_url` isn't a valid URL or the HTTP client can't be built.
+    pub(crate) fn new(
+        forge: &'static str,
+        accept: &'static str,
+        auth: Option<(HeaderName, HeaderValue)>,
+        options: ClientOptions,
+    ) -> Result<Self> {
+        if auth.is_none() {
+            debug!(
+                "No {} token configured. Sending unauthenticated requests",
+                forge
+            );
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(accept));
+        reqwest::Url::parse(&options.api_url)
+            .map_err(|e| format!("invalid API URL '{}': {}", options.api_url, e))?;
+        let client = Client::builder()
+            .user_agent("rust-triage-tracker")
+            .default_headers(headers)
+            .connect_timeout(options.connect_timeout)
+            .timeout(options.timeout)
+            .build()?;
+        Ok(Self {
+            client,
+            auth,
+            options,
+        })
+    }
+
+    /// Whether requests are sent with a token
+    pub(crate) fn is_authenticated(&self) -> bool {
+        self.auth.is_some()
+    }
+
+    /// The API URL for `path` with the query parameters `params`
+    pub(crate) fn url(&self, path: &str, params: &[(&str, String)]) -> Result<reqwest::Url> {
+        let url = format!("{}/{}", self.options.api_url.trim_end_matches('/'), path);
+        reqwest::Url::parse_with_params(&url, params)
+            .map_err(|e| format!("invalid URL '{}': {}", url, e).into())
+    }
+
+    /// Send a request and decode its response, retrying it as `options` say
+    ///
+    /// `check_rate_limit` tells responses about rate limits apart from others.
+    pub(crate) async fn send<T, C, Fut>(
+        &self,
+        method: Method,
+        url: reqwest::Url,
+        body: Option<&serde_json::Value>,
+        check_rate_limit: C,
+    ) -> Result<(T, HeaderMap)>
+    where
+        T: serde::de::DeserializeOwned,
+        C: Fn(reqwest::Response) -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<reqwest::Response, Failure>>,
+    {
+        self.send_with_retries(&url, || {
+            self.try_send(method.clone(), url.clone(), body, &check_rate_limit)
+        })
+        .await
+    }
+
+    /// Make a single attempt at a request and decode its response
+    pub(crate) async fn try_send<T, C, Fut>(
+        &self,
+        method: Method,
+        url: reqwest::Url,
+        body: Option<&serde_json::Value>,
+        check_rate_limit: C,
+    ) -> std::result::Result<(T, HeaderMap), Failure>
+    where
+        T: serde::de::DeserializeOwned,
+        C: Fn(reqwest::Response) -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<reqwest::Response, Failure>>,
+    {
+        let mut request = self.client.request(method, url);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        if let Some((name, value)) = &self.auth {
+            request = request.header(name, value.clone());
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Failure::Transient(e.into()))?;
+        let response = check_rate_limit(response).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let response = response.error_for_status().map_err(|e| {
+            if is_transient(status) {
+                Failure::Transient(e.into())
+            } else {
+                Failure::Fatal(e.into())
+            }
+        })?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Failure::Transient(e.into()))?;
+        let decoded = serde_json::from_slice(&body).map_err(|e| Failure::Transient(e.into()))?;
+        Ok((decoded, headers))
+    }
+
+    /// Make attempts at a request until one succeeds
+    ///
+    /// Transient failures are retried, and rate limits are waited on if configured.
+    pub(crate) async fn send_with_retries<T, F, Fut>(
+        &self,
+        url: &reqwest::Url,
+        attempt: F,
+    ) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<T, Failure>>,
+    {
+        debug!("Fetching '{}'", url);
+        let mut num_retries = 0;
+        loop {
+            self.options.budget.spend()?;
+            let error = match attempt().await {
+                Ok(result) => return Ok(result),
+                Err(Failure::RateLimited(limit)) if self.options.wait_on_rate_limit => {
+                    wait_for_rate_limit(limit).await;
+                    continue;
+                }
+                Err(Failure::RateLimited(limit)) => return Err(Error::RateLimited(limit)),
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Transient(e)) => e,
+            };
+            num_retries += 1;
+            if num_retries > self.options.retry.max_retries {
+                return Err(error);
+            }
+            let backoff = self.options.retry.backoff(num_retries);
+            debug!(
+                "Error fetching '{}': {}\nRetrying in {:?}...",
+                url, error, backoff
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+impl<T> Page<T> {
+    pub(crate) fn new(items: Vec<T>, links: Links) -> Self {
+        Self {
+            items,
+            next: links.next,
+            last: links.last,
+        }
+    }
+}
+
+/// The page numbers found in a `Link` header
+#[derive(Debug, Default)]
+pub(crate) struct Links {
+    next: Option<u32>,
+    last: Option<u32>,
+}
+
+impl Links {
+    /// Parse a header of the form `<https://...?page=2>; rel="next", <https://...?page=5>; rel="last"`
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let mut links = Self::default();
+        let header = match headers.get(LINK).and_then(|h| h.to_str().ok()) {
+            Some(h) => h,
+            None => return links,
+        };
+        for link in header.split(',') {
+            let mut parts = link.split(';');
+            let url = parts
+                .next()
+                .map(|u| u.trim().trim_start_matches('<').trim_end_matches('>'))
+                .and_then(|u| reqwest::Url::parse(u).ok());
+            let page = url.and_then(|u| {
+                u.query_pairs()
+                    .find(|(k, _)| k == "page")
+                    .and_then(|(_, v)| v.parse().ok())
+            });
+            for param in parts {
+                match param.trim() {
+                    r#"rel="next""# => links.next = page,
+                    r#"rel="last""# => links.last = page,
+                    _ => {}
+                }
+            }
+        }
+        links
+    }
+}
+
+/// Whether a server error status is usually temporary
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Sleep until `limit` resets, telling the user which forge is waited on
+async fn wait_for_rate_limit(limit: RateLimit) {
+    let now = chrono::Utc::now();
+    let retry_at = limit
+        .retry_at
+        .unwrap_or_else(|| now + chrono::Duration::seconds(SECONDARY_RATE_LIMIT_WAIT));
+    // Wait an extra second to not race the server's clock
+    let wait = (retry_at - now).max(chrono::Duration::zero()) + chrono::Duration::seconds(1);
+    eprintln!(
+        "Hit {} {}. Waiting {} seconds before continuing...",
+        limit.forge,
+        limit,
+        wait.num_seconds()
+    );
+    tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+}
+
+/// The value of the header `name`, if it is present and parses
+pub(crate) fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// The time a `Retry-After` header in seconds points at, if there is one
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<chrono::DateTime<chrono::Utc>> {
+    header::<i64>(headers, reqwest::header::RETRY_AFTER.as_str())
+        .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs))
+}
+
+/// The time a header holding a Unix timestamp points at, if there is one
+pub(crate) fn timestamp(headers: &HeaderMap, name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    header::<i64>(headers, name).map(|secs| {
+        chrono::DateTime::from_utc(chrono::NaiveDateTime::from_timestamp(secs, 0), chrono::Utc)
+    })
+}
+
+/// The start of `date` in the RFC 3339 form the forges expect
+pub(crate) fn start_of(date: chrono::NaiveDate) -> String {
+    format!("{}T00:00:00Z", date.format("%Y-%m-%d"))
+}
+
+/// The base URL of the web interface belonging to an API URL ending in `api_path`
+///
+/// E.g. `https://<host>/api/v4` maps to `https://<host>` for `/api/v4`. Any
+/// other URL (e.g. a local mock server) is used as is.
+pub(crate) fn web_url(api_url: &str, api_path: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    api_url.strip_suffix(api_path).unwrap_or(api_url).to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(link: &str) -> (Option<u32>, Option<u32>) {
+        let mut headers = HeaderMap::new();
+        headers.insert(LINK, HeaderValue::from_str(link).unwrap());
+        let links = Links::from_headers(&headers);
+        (links.next, links.last)
+    }
+
+    const ISSUES: &str = "https://api.github.com/repositories/724712/issues";
+
+    #[test]
+    fn first_page() {
+        let header = format!(
+            r#"<{0}?per_page=100&page=2>; rel="next", <{0}?per_page=100&page=51>; rel="last""#,
+            ISSUES
+        );
+        assert_eq!(links(&header), (Some(2), Some(51)));
+    }
+
+    #[test]
+    fn middle_page() {
+        let header = format!(
+            r#"<{0}?page=4>; rel="prev", <{0}?page=6>; rel="next", <{0}?page=51>; rel="last", <{0}?page=1>; rel="first""#,
+            ISSUES
+        );
+        assert_eq!(links(&header), (Some(6), Some(51)));
+    }
+
+    #[test]
+    fn last_page() {
+        let header = format!(
+            r#"<{0}?page=50>; rel="prev", <{0}?page=1>; rel="first""#,
+            ISSUES
+        );
+        assert_eq!(links(&header), (None, None));
+    }
+
+    #[test]
+    fn only_page() {
+        // A list that fits on one page has no `Link` header
+        let links = Links::from_headers(&HeaderMap::new());
+        assert_eq!((links.next, links.last), (None, None));
+    }
+
+    #[test]
+    fn missing_rel() {
+        let header = format!(r#"<{0}?page=2>, <{0}?page=51>; rel="last""#, ISSUES);
+        assert_eq!(links(&header), (None, Some(51)));
+    }
+
+    #[test]
+    fn missing_page() {
+        let header = format!(
+            r#"<{0}?per_page=100>; rel="next", <not a url>; rel="last""#,
+            ISSUES
+        );
+        assert_eq!(links(&header), (None, None));
+    }
+}
@@ -0,0 +1,50 @@
+//! Tracking of issue triage in GitHub, GitLab and Gitea repositories
+//!
+//! This crate answers two questions about a repository:
+//!
+//! * how many issues were opened and closed on a given date ([`Issues::for_date`])
+//! * which open issues have not been commented on for a while ([`TriageQuery`])
+//!
+//! Data is fetched from an [`IssueSource`], such as a [`github::GitHub`],
+//! [`gitlab::GitLab`] or [`gitea::Gitea`] client, and cached in a SQLite
+//! database (see [`Cache`]), ahead of time if need be (see [`Backfill`]).
+//!
+//! ```no_run
+//! use triage_tracker::github::{self, GitHub};
+//! use triage_tracker::{Cache, ClientOptions, Issues};
+//!
+//! # async fn example() -> triage_tracker::Result<()> {
+//! let repo = "rust-lang/cargo".parse()?;
+//! let cache = Cache::open(&Cache::default_dir("github", github::DEFAULT_API_URL, &repo)?)?;
+//! let github = GitHub::new(repo, None, ClientOptions::default())?;
+//! let date = chrono::NaiveDate::from_ymd(2021, 6, 7);
+//! let issues = Issues::for_date(&github, &cache, date).await?;
+//! println!("Net change on {}: {}", date, issues.diff());
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+mod backfill;
+mod cache;
+mod closings;
+pub mod error;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+mod http;
+mod model;
+mod repo;
+mod source;
+mod triage;
+
+pub use backfill::{Backfill, BackfillReport};
+pub use cache::{Cache, CacheStats, CacheType, CachedList, LegacyImport};
+pub use closings::Issues;
+pub use error::{Error, Result};
+pub use http::{ClientOptions, RequestBudget, RetryPolicy, Token};
+pub use model::{Actor, Comment, Event, EventId, Issue, PullRequest};
+pub use repo::Repo;
+pub use source::{IssueSource, Page};
+pub use triage::{Activity, TriageQuery, TriageReport, UntriagedIssue};
@@ -1,300 +1,683 @@
-use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use log::debug;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use structopt::StructOpt;
+use triage_tracker::{
+    gitea, github, gitlab, Backfill, Cache, ClientOptions, Error, IssueSource, Issues, Repo,
+    RequestBudget, Result, Token, TriageQuery,
+};
 
-mod error;
-mod github;
+mod chart;
+mod config;
 mod gui;
+mod output;
+mod report;
 
-use error::Error;
+use output::Format;
 
 #[derive(StructOpt, Debug)]
 struct App {
+    /// The repository to track, given as `owner/name` [default: rust-lang/rust]
+    #[structopt(long, global = true, env = "TRIAGE_TRACKER_REPO")]
+    repo: Option<Repo>,
+    /// Where the repository is hosted: `github`, `gitlab` or `gitea` (also for Forgejo) [default: github]
+    #[structopt(long, global = true, env = "TRIAGE_TRACKER_FORGE")]
+    forge: Option<Forge>,
+    /// Base URL of the forge's API, e.g. `https://ghe.example.com/api/v3`; `GITHUB_API_URL` is used for GitHub too [default: https://api.github.com, https://gitlab.com/api/v4 or https://codeberg.org/api/v1]
+    #[structopt(long, global = true, env = "TRIAGE_TRACKER_API_URL")]
+    api_url: Option<String>,
+    /// Path to a file containing a token [default: $GITHUB_TOKEN, $GITLAB_TOKEN or $GITEA_TOKEN]
+    #[structopt(long, global = true)]
+    token_file: Option<PathBuf>,
+    /// Wait for rate limits to reset instead of stopping early
+    #[structopt(long, global = true)]
+    wait_on_rate_limit: bool,
+    /// Answer from the cache alone, without sending any requests
+    #[structopt(long, global = true)]
+    offline: bool,
+    /// Timeout in seconds for a single request [default: 30]
+    #[structopt(long, global = true)]
+    timeout: Option<u64>,
+    /// How often to retry requests that failed for transient reasons [default: 3]
+    #[structopt(long, global = true)]
+    max_retries: Option<u32>,
+    /// Directory to cache fetched data in [default: $XDG_CACHE_HOME/triage-tracker/<forge>/<host>/<owner>/<name>]
+    #[structopt(long, global = true, env = "TRIAGE_TRACKER_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Path to an optional TOML config file
+    #[structopt(long, global = true, default_value = "triage-tracker.toml")]
+    config: PathBuf,
     #[structopt(subcommand)]
     command: Command,
 }
 
+/// The kind of server hosting a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Forge {
+    GitHub,
+    GitLab,
+    #[serde(alias = "forgejo")]
+    Gitea,
+}
+
+impl Forge {
+    /// The name the forge is selected by, also used in cache paths
+    fn name(self) -> &'static str {
+        match self {
+            Self::GitHub => "github",
+            Self::GitLab => "gitlab",
+            Self::Gitea => "gitea",
+        }
+    }
+
+    fn default_api_url(self) -> &'static str {
+        match self {
+            Self::GitHub => github::DEFAULT_API_URL,
+            Self::GitLab => gitlab::DEFAULT_API_URL,
+            Self::Gitea => gitea::DEFAULT_API_URL,
+        }
+    }
+
+    /// The environment variable a token for this forge is read from
+    fn token_var(self) -> &'static str {
+        match self {
+            Self::GitHub => "GITHUB_TOKEN",
+            Self::GitLab => "GITLAB_TOKEN",
+            Self::Gitea => "GITEA_TOKEN",
+        }
+    }
+}
+
+impl std::str::FromStr for Forge {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::GitHub),
+            "gitlab" => Ok(Self::GitLab),
+            "gitea" | "forgejo" => Ok(Self::Gitea),
+            _ => Err(format!(
+                "unknown forge '{}': expected `github`, `gitlab` or `gitea`",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Forge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::GitHub => "GitHub",
+            Self::GitLab => "GitLab",
+            Self::Gitea => "Gitea",
+        })
+    }
+}
+
 #[derive(StructOpt, Debug)]
 enum Command {
     /// Track net closings of issues
     Closings(ClosingsCommand),
     /// Track triaged issues
     Triaged(TriagedCommand),
+    /// Write a Markdown report of closings and stale issues
+    Report(ReportCommand),
+    /// Draw a chart of net closings to an SVG or PNG file
+    Chart(ChartCommand),
+    /// Fetch and cache a range of dates ahead of time
+    Backfill(BackfillCommand),
+    /// Inspect and manage the cache
+    Cache(CacheCommand),
+}
+
+#[derive(StructOpt, Debug)]
+struct ReportCommand {
+    /// Only list stale issues having all of these labels
+    tags: Vec<String>,
+    /// The most recent date to count closings for [default: yesterday]
+    #[structopt(short, long)]
+    start: Option<chrono::NaiveDate>,
+    /// The earliest date to count closings for [default: six days before --start]
+    #[structopt(short, long)]
+    end: Option<chrono::NaiveDate>,
+    /// List open issues without activity since this date [default: a year ago]
+    #[structopt(long)]
+    since: Option<chrono::NaiveDate>,
+    /// Group stale issues only by the labels starting with this prefix, e.g. `T-`
+    #[structopt(long)]
+    group_by: Option<String>,
+    /// How many people to list as top openers and closers
+    #[structopt(long, default_value = "5")]
+    top: usize,
+    /// Use the GraphQL API to find stale issues, which needs a token but far fewer requests
+    #[structopt(long)]
+    graphql: bool,
+    #[structopt(flatten)]
+    options: ClosingsOptions,
+}
+
+#[derive(StructOpt, Debug)]
+struct ChartCommand {
+    /// The most recent date to chart
+    #[structopt(short, long)]
+    start: chrono::NaiveDate,
+    /// The earliest date to chart
+    #[structopt(short, long)]
+    end: chrono::NaiveDate,
+    /// The file to write, whose extension selects the format: `.svg` or `.png`
+    #[structopt(short, long)]
+    output: PathBuf,
+    /// What to chart: `daily` opened, closed and net issues, or the `cumulative` change
+    #[structopt(long, default_value = "daily")]
+    series: chart::Series,
+    /// How to draw the values: `bar` or `line`
+    #[structopt(long, default_value = "bar")]
+    style: chart::Style,
+    /// Width of the chart in pixels
+    #[structopt(long, default_value = "1024")]
+    width: u32,
+    /// Height of the chart in pixels
+    #[structopt(long, default_value = "512")]
+    height: u32,
+    /// The title above the chart [default: describes the series and repository]
+    #[structopt(long)]
+    title: Option<String>,
+    #[structopt(flatten)]
+    options: ClosingsOptions,
+}
+
+#[derive(StructOpt, Debug)]
+struct BackfillCommand {
+    /// The first date to fetch
+    #[structopt(long)]
+    from: chrono::NaiveDate,
+    /// The last date to fetch
+    #[structopt(long)]
+    to: chrono::NaiveDate,
+    /// Stop after sending this many requests
+    #[structopt(long)]
+    max_requests: Option<u32>,
+    /// Also check when open issues were last commented on, like `triaged --since <from>`
+    #[structopt(long)]
+    triage: bool,
+    /// Only check issues having all of these labels
+    #[structopt(long = "label", requires = "triage")]
+    labels: Vec<String>,
+    #[structopt(flatten)]
+    options: ClosingsOptions,
+}
+
+#[derive(StructOpt, Debug)]
+enum CacheCommand {
+    /// List the cached dates and whether they are complete
+    List,
+    /// Show how much is cached
+    Stats,
+    /// Remove data older than a date
+    Prune {
+        #[structopt(long)]
+        older_than: chrono::NaiveDate,
+    },
+    /// Remove cached data so that it is fetched again, by default all of it
+    Clear {
+        /// Only remove the issues and events of a date
+        #[structopt(long)]
+        date: Vec<chrono::NaiveDate>,
+        /// Only forget when an issue was last active
+        #[structopt(long)]
+        issue: Vec<u32>,
+    },
+    /// Check the cache for corruption and entries that can't be read
+    Verify,
+    /// Print everything cached as JSON
+    Export,
 }
 
 #[derive(StructOpt, Debug)]
 enum ClosingsCommand {
     /// Print open and closed issues for a specific date
-    Date { date: String },
+    Date {
+        date: chrono::NaiveDate,
+        /// How to print the results: `text`, `json`, `ndjson`, `csv` or `html`
+        #[structopt(long, default_value = "text")]
+        format: Format,
+        #[structopt(flatten)]
+        options: ClosingsOptions,
+    },
     /// Print open and closed issues for a range of dates
     Range {
         #[structopt(short, long)]
-        start: String,
+        start: chrono::NaiveDate,
         #[structopt(short, long)]
-        end: String,
+        end: chrono::NaiveDate,
+        /// How to print the results: `text`, `json`, `ndjson`, `csv` or `html`
+        #[structopt(long, default_value = "text")]
+        format: Format,
+        #[structopt(flatten)]
+        options: ClosingsOptions,
     },
 }
 
+impl ClosingsCommand {
+    fn options(&self) -> &ClosingsOptions {
+        match self {
+            Self::Date { options, .. } | Self::Range { options, .. } => options,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+struct ClosingsOptions {
+    /// Where opened and closed issues are looked up: `events` or `search`
+    #[structopt(long, default_value = "events")]
+    backend: Backend,
+}
+
+/// Where opened and closed issues for a date are looked up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    /// Scan the repository's issue events and issues lists
+    Events,
+    /// Use the search API to find issues created or closed on a date
+    Search,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "events" => Ok(Self::Events),
+            "search" => Ok(Self::Search),
+            _ => Err(format!(
+                "unknown backend '{}': expected `events` or `search`",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct TriagedCommand {
     tags: Vec<String>,
     #[structopt(short, long)]
-    since: Option<String>,
+    since: Option<chrono::NaiveDate>,
+    /// Use the GraphQL API, which needs a token but far fewer requests
+    #[structopt(long)]
+    graphql: bool,
+    /// How to print the results: `text`, `json`, `ndjson`, `csv` or `markdown`
+    #[structopt(long, default_value = "text")]
+    format: Format,
 }
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
     let app = App::from_args();
-    let result = match app.command {
-        Command::Closings(ClosingsCommand::Date { date }) => {
-            let date = date.parse::<chrono::NaiveDate>().unwrap();
-            handle_date(date).await
-        }
-        Command::Closings(ClosingsCommand::Range { start, end }) => {
-            let start = start.parse::<chrono::NaiveDate>().unwrap();
-            let end = end.parse::<chrono::NaiveDate>().unwrap();
-            handle_range(start, end).await
-        }
-        Command::Triaged(TriagedCommand { tags, since }) => {
-            let since = since.map(|s| s.parse::<chrono::NaiveDate>().unwrap());
-            handle_triaged(tags, since).await
-        }
-    };
-    if let Err(e) = result {
+    if let Err(e) = run(app).await {
         eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
 }
 
-struct TriageCache {
-    internal: HashMap<u32, TriageCacheLine>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct TriageCacheLine {
-    /// The type of activity.
-    activity: Activity,
-    last_checked: chrono::DateTime<chrono::Utc>,
-}
-
-// Type of activity we know about an issue
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-enum Activity {
-    /// We have not seen an activity since the given date, but
-    /// there might be some before
-    NoActivitySince(chrono::NaiveDate),
-    /// The last comment was on the given date
-    LastCommented(chrono::NaiveDate),
+async fn run(app: App) -> Result<()> {
+    let config = config::Config::from_file(&app.config).await?;
+    let repo = app.repo.or_else(|| config.repo.clone()).unwrap_or_default();
+    let forge = app.forge.or(config.forge).unwrap_or(Forge::GitHub);
+    debug!("Tracking repository '{}' on {}", repo, forge);
+    let api_url = app
+        .api_url
+        .or_else(|| match forge {
+            // GitHub Actions sets this in every job, including ones tracking other forges
+            Forge::GitHub => std::env::var("GITHUB_API_URL").ok(),
+            Forge::GitLab | Forge::Gitea => None,
+        })
+        .or_else(|| config.api_url.clone())
+        .unwrap_or_else(|| forge.default_api_url().to_owned());
+    let cache_dir = match app.cache_dir.or_else(|| config.cache_dir.clone()) {
+        Some(dir) => dir,
+        None => Cache::default_dir(forge.name(), &api_url, &repo)?,
+    };
+    let command = match app.command {
+        Command::Cache(command) => return handle_cache(command, &cache_dir, &repo),
+        command => command,
+    };
+    let offline = app.offline || config.offline;
+    if offline && matches!(command, Command::Backfill(_)) {
+        return Err("backfill can't run offline".into());
+    }
+    let cache = if offline {
+        // Without a cache there is nothing to answer from
+        Cache::open(&cache_dir)?
+    } else {
+        open_cache(&cache_dir)?
+    };
+    import_legacy(&cache, &repo);
+    let token = token(forge, app.token_file, &config).await?;
+    let mut options = ClientOptions {
+        api_url,
+        wait_on_rate_limit: app.wait_on_rate_limit || config.wait_on_rate_limit,
+        ..Default::default()
+    };
+    if let Some(timeout) = app.timeout.or(config.timeout) {
+        options.timeout = std::time::Duration::from_secs(timeout);
+    }
+    if let Some(timeout) = config.connect_timeout {
+        options.connect_timeout = std::time::Duration::from_secs(timeout);
+    }
+    if let Some(max_retries) = app.max_retries.or(config.max_retries) {
+        options.retry.max_retries = max_retries;
+    }
+    if let Command::Backfill(BackfillCommand {
+        max_requests: Some(max_requests),
+        ..
+    }) = command
+    {
+        options.budget = RequestBudget::new(max_requests);
+    }
+    let budget = options.budget.clone();
+    let source: Box<dyn IssueSource> = match forge {
+        Forge::GitHub => Box::new(github::GitHub::new(repo, token, options)?),
+        Forge::GitLab => Box::new(gitlab::GitLab::new(repo, token, options)?),
+        Forge::Gitea => Box::new(gitea::Gitea::new(repo, token, options)?),
+    };
+    let backend = match &command {
+        Command::Closings(command) => command.options().backend,
+        Command::Report(command) => command.options.backend,
+        Command::Chart(command) => command.options.backend,
+        Command::Backfill(command) => command.options.backend,
+        _ => Backend::Events,
+    };
+    let search;
+    let closings_source = match backend {
+        Backend::Events => source.as_ref(),
+        Backend::Search => {
+            let github = source
+                .as_github()
+                .ok_or("the search backend is only available for GitHub")?;
+            search = github::Search(github);
+            &search as &dyn IssueSource
+        }
+    };
+    match command {
+        Command::Closings(command) => match command {
+            ClosingsCommand::Date { date, format, .. } => {
+                handle_date(closings_source, &cache, date, offline, format).await
+            }
+            ClosingsCommand::Range {
+                start, end, format, ..
+            } => handle_range(closings_source, &cache, start, end, offline, format).await,
+        },
+        Command::Triaged(TriagedCommand {
+            tags,
+            since,
+            graphql,
+            format,
+        }) => {
+            let query = TriageQuery::new()
+                .labels(&tags)
+                .graphql(graphql)
+                .offline(offline);
+            handle_triaged(forge, source.as_ref(), &cache, query, since, format).await
+        }
+        Command::Report(command) => {
+            let query = TriageQuery::new()
+                .labels(&command.tags)
+                .graphql(command.graphql)
+                .offline(offline);
+            let report = Report {
+                forge,
+                source: source.as_ref(),
+                closings_source,
+                cache: &cache,
+                offline,
+            };
+            report.run(command, query).await
+        }
+        Command::Chart(command) => handle_chart(closings_source, &cache, command, offline).await,
+        Command::Backfill(command) => {
+            handle_backfill(forge, source.as_ref(), closings_source, &cache, command).await?;
+            println!("{} requests sent", budget.sent());
+            Ok(())
+        }
+        Command::Cache(_) => unreachable!("the cache command doesn't need a source"),
+    }
 }
 
-/// The result of looking up in the cache
-enum CacheResult {
-    Fresh(Activity),
-    Stale(Activity),
-    NotFound,
+/// Open the cache in `dir`
+///
+/// If the cache can't be opened, the run goes on without caching.
+fn open_cache(dir: &Path) -> Result<Cache> {
+    Cache::open(dir).or_else(|e| {
+        eprintln!("Warning: {}; not caching this run", e);
+        Cache::in_memory()
+    })
 }
 
-impl TriageCache {
-    async fn from_disk() -> Result<Self> {
-        let internal = match tokio::fs::read_to_string("./database/triage.json").await {
-            Ok(f) => serde_json::from_str::<HashMap<u32, TriageCacheLine>>(&f).ok(),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
-            Err(e) => return Err(e.into()),
-        };
-        if let None = internal {
-            if let Err(e) = tokio::fs::write("./database/triage.json", "{}").await {
-                debug!("Writing empty cache failed: {}", e);
+/// Import what older versions cached in `database/` below the current directory
+/// into a newly created cache
+fn import_legacy(cache: &Cache, repo: &Repo) {
+    let mut dirs = vec![Path::new("database").join(&repo.owner).join(&repo.name)];
+    // Before other repositories could be tracked, rust-lang/rust was cached in `database/` itself
+    if *repo == Repo::default() {
+        dirs.push(PathBuf::from("database"));
+    }
+    for dir in dirs {
+        match cache.import_legacy(&dir) {
+            Ok(import) => {
+                if import.lists > 0 || import.activities > 0 {
+                    eprintln!(
+                        "Imported {} lists and the activity of {} issues from '{}'",
+                        import.lists,
+                        import.activities,
+                        dir.display()
+                    );
+                }
+                if import.skipped > 0 {
+                    eprintln!(
+                        "Warning: skipped {} unreadable files in '{}'",
+                        import.skipped,
+                        dir.display()
+                    );
+                }
             }
+            Err(e) => eprintln!(
+                "Warning: failed to import the old cache in '{}': {}",
+                dir.display(),
+                e
+            ),
         }
-
-        Ok(Self {
-            internal: internal.unwrap_or_default(),
-        })
     }
+}
 
-    /// Get the cached activity for an issue
-    fn get(&self, issue_number: &u32, ttl: Option<chrono::Duration>) -> CacheResult {
-        self.internal
-            .get(issue_number)
-            .map(|l| {
-                debug!("Issue #{} found in triage cache", issue_number);
-                let now = chrono::Utc::now();
-                let ago = ttl.map(|ttl| now - ttl);
-                if ago.map(|ago| l.last_checked < ago).unwrap_or(false) {
-                    CacheResult::Stale(l.activity)
-                } else {
-                    CacheResult::Fresh(l.activity)
-                }
-            })
-            .unwrap_or(CacheResult::NotFound)
+/// Find the token to use
+///
+/// In order of precedence the token is taken from `--token-file`, the `GITHUB_TOKEN`
+/// (or `GITLAB_TOKEN` or `GITEA_TOKEN`) environment variable, and finally the `token_file` or
+/// `token` config settings.
+async fn token(
+    forge: Forge,
+    token_file: Option<PathBuf>,
+    config: &config::Config,
+) -> Result<Option<Token>> {
+    if let Some(path) = token_file {
+        return Ok(Some(Token::from_file(&path).await?));
     }
-
-    fn insert(&mut self, issue_number: u32, activity: Activity) {
-        debug!("Inserting issue #{} into cache", issue_number);
-        let cache_line = TriageCacheLine {
-            activity,
-            last_checked: chrono::Utc::now(),
-        };
-        self.internal.insert(issue_number, cache_line);
+    if let Some(token) = std::env::var(forge.token_var())
+        .ok()
+        .and_then(|t| Token::new(&t))
+    {
+        debug!("Using token from {}", forge.token_var());
+        return Ok(Some(token));
     }
-
-    async fn flush(&self) -> Result<()> {
-        let cache = serde_json::to_vec(&self.internal).unwrap();
-        if let Err(e) = tokio::fs::write("./database/triage.json", cache).await {
-            debug!("Writting cache failed: {}", e);
-        }
-        Ok(())
+    if let Some(path) = &config.token_file {
+        return Ok(Some(Token::from_file(path).await?));
     }
+    Ok(config.token.clone())
 }
 
-async fn handle_triaged(tags: Vec<String>, since: Option<chrono::NaiveDate>) -> Result<()> {
-    let mut untriaged = Vec::new();
-    let mut cache = TriageCache::from_disk().await?;
-    let since = since.unwrap_or_else(|| {
-        let today = chrono::Local::today().naive_local();
-        today - chrono::Duration::days(365)
-    });
-    let result = match perform_triage_loop(&tags, since, &mut untriaged, &mut cache).await {
-        r @ Ok(()) | r @ Err(Error::RateLimited) => {
-            cache.flush().await?;
-            r
-        }
-        Err(e) => return Err(e),
-    };
-    if let Err(Error::RateLimited) = result {
-        eprintln!("Error: hit Github rate limiting. Stop early");
+async fn handle_triaged(
+    forge: Forge,
+    source: &dyn IssueSource,
+    cache: &Cache,
+    mut query: TriageQuery,
+    since: Option<chrono::NaiveDate>,
+    format: Format,
+) -> Result<()> {
+    if let Some(since) = since {
+        query = query.since(since);
+    }
+    let report = query.run(source, cache).await?;
+    if let Some(limit) = report.rate_limited {
+        eprintln!("Error: hit {} {}. Stop early", forge, limit);
     }
+    if format != Format::Text {
+        return output::print_triaged(format, source, &report);
+    }
+    if report.open_issues_missing {
+        println!("Nothing cached: run without --offline to fetch the open issues");
+        return Ok(());
+    }
+    let untriaged = report.untriaged;
     println!(
         "{} untriaged issue{} found:",
         untriaged.len(),
         if untriaged.len() != 1 { "s" } else { "" }
     );
     for issue in untriaged {
-        println!("https://github.com/rust-lang/rust/issues/{}", issue.number);
+        println!("{}", source.issue_url(issue.issue.number));
+    }
+    let missing = report.missing;
+    if !missing.is_empty() {
+        println!(
+            "Partial result: the activity of {} issue{} isn't cached:",
+            missing.len(),
+            if missing.len() != 1 { "s" } else { "" }
+        );
+        for issue in missing {
+            println!("{}", source.issue_url(issue.number));
+        }
     }
     Ok(())
 }
 
-/// Check which issues given with `tags` were last active before the `last_active_yard_stick`
-async fn perform_triage_loop(
-    tags: &[String],
-    last_active_yard_stick: chrono::NaiveDate,
-    untriaged: &mut Vec<Issue>,
-    cache: &mut TriageCache,
-) -> Result<()> {
-    for page in 1.. {
-        let issues = github::fetch_issue_page(
-            page,
-            100,
-            &tags,
-            github::SortedBy::Comments,
-            github::Direction::OldestFirst,
-        )
-        .await?;
-        if issues.is_empty() {
-            debug!("No more issues in page. Breaking...");
+/// Look up the issues for `date`, or `None` if running offline and the date isn't cached
+async fn issues_for_date(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    date: chrono::NaiveDate,
+    offline: bool,
+) -> Result<Option<Issues>> {
+    if offline {
+        Issues::cached_for_date(source, cache, date)
+    } else {
+        Issues::for_date(source, cache, date).await.map(Some)
+    }
+}
+
+/// Look up the issues for each date from `start` back to `end`
+async fn issues_for_range(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+    offline: bool,
+) -> Result<Vec<(chrono::NaiveDate, Option<Issues>)>> {
+    if end >= start {
+        return Err("--start must be more recent than --end".into());
+    }
+    let mut issues = Vec::new();
+    let mut date = start;
+    loop {
+        issues.push((date, issues_for_date(source, cache, date, offline).await?));
+        date = date.pred();
+        if date == end.pred() {
             break;
         }
-        for issue in issues {
-            if issue.is_pull_request() {
-                continue;
-            }
-            if issue.comments == 0 {
-                // Issue has no comments
-                debug!("Issue #{} has no comments", issue.number);
-                let created_at = issue.created_at.date().naive_local();
-                let issue_number = issue.number;
-                if created_at < last_active_yard_stick {
-                    debug!(
-                        "Issue #{} without comments was created before selected date",
-                        issue_number
-                    );
-                    untriaged.push(issue);
-                }
-                continue;
-            }
-
-            match cache.get(&issue.number, Some(chrono::Duration::days(1))) {
-                CacheResult::Fresh(Activity::LastCommented(last_comment)) => {
-                    let issue_number = issue.number;
-                    let direction = if last_comment < last_active_yard_stick {
-                        untriaged.push(issue);
-                        "before"
-                    } else {
-                        "after"
-                    };
-                    debug!(
-                        "Issue #{} was last commented on ({:?}) {} the yard stick ({:?})",
-                        issue_number, last_comment, direction, last_active_yard_stick
-                    );
-                    // We have an answer so go on to next issue
-                    continue;
-                }
-                CacheResult::Fresh(Activity::NoActivitySince(no_activity_since)) => {
-                    if no_activity_since <= last_active_yard_stick {
-                        debug!(
-                            "Issue #{} was last active (sometime before {:?}) before the yard stick ({:?})",
-                            issue.number, no_activity_since, last_active_yard_stick
-                        );
-
-                        untriaged.push(issue);
-
-                        // We have an answer so go on to next issue
-                        continue;
-                    } else {
-                        debug!(
-                            "The yard stick ({:?}) is before when we have visibility ({:?}) on issue #{}",
-                            last_active_yard_stick, no_activity_since, issue.number
-                        );
-                        // We don't know when the issue was last active, we need to determine that
-                    }
-                }
-                CacheResult::Stale(Activity::LastCommented(last_commented))
-                    if last_commented > last_active_yard_stick =>
-                {
-                    // Even though the result is stale, we still know that there is a comment more recent than
-                    // the yard stick. It's possible there's an even *more* recent comment, but that's not relevant.
-                    continue;
-                }
-                _ => {
-                    debug!("Issue #{} not found in cache.", issue.number);
-                }
-            }
-
-            debug!(
-                "State of issue #{} could not be determined from cache. Fetching comments...",
-                issue.number
-            );
+    }
+    Ok(issues)
+}
 
-            let comments =
-                github::fetch_comment_page(issue.number, 1, 100, Some(last_active_yard_stick))
-                    .await?;
-            if comments.is_empty() {
-                cache.insert(
-                    issue.number,
-                    Activity::NoActivitySince(last_active_yard_stick),
-                );
-                untriaged.push(issue);
-            } else if comments.len() < 100 {
-                cache.insert(
-                    issue.number,
-                    Activity::LastCommented(
-                        comments.last().unwrap().created_at.naive_local().date(),
-                    ),
-                );
-            } else {
-                todo!("More than a 100 comments made in past year");
-            }
+async fn handle_chart(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    command: ChartCommand,
+    offline: bool,
+) -> Result<()> {
+    let extension = command
+        .output
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let is_png = match extension.as_deref() {
+        Some("svg") => false,
+        Some("png") => true,
+        _ => {
+            return Err(format!(
+                "can't tell the chart format of '{}': expected a `.svg` or `.png` file",
+                command.output.display()
+            )
+            .into())
         }
+    };
+    let days = issues_for_range(source, cache, command.start, command.end, offline).await?;
+    let closings = chart::Closings::new(&days);
+    let missing = closings.missing();
+    if missing > 0 {
+        eprintln!(
+            "Warning: {} date{} not cached, charted as no change",
+            missing,
+            if missing != 1 { "s" } else { "" }
+        );
+    }
+    let (series, style) = (command.series, command.style);
+    let title = command.title.unwrap_or_else(|| match series {
+        chart::Series::Daily => format!("Net closings in {}", source.repo()),
+        chart::Series::Cumulative => format!("Change in open issues in {}", source.repo()),
+    });
+    let size = (command.width, command.height);
+    if is_png {
+        chart::to_png(&command.output, size, |area| {
+            chart::draw(area, &closings, series, style, &title)
+        })
+    } else {
+        let svg = chart::to_svg(size, |area| {
+            chart::draw(area, &closings, series, style, &title)
+        })?;
+        std::fs::write(&command.output, svg)?;
+        Ok(())
     }
-    Ok(())
 }
 
-async fn handle_date(date: chrono::NaiveDate) -> Result<()> {
-    let items = Issues::for_date(date).await?;
+/// What it means for a date's result to be partial, as printed by `closings`
+const UNSETTLED: &str =
+    "fetched before this date settled (within 24h of its end); rerun later to refresh";
 
+async fn handle_date(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    date: chrono::NaiveDate,
+    offline: bool,
+    format: Format,
+) -> Result<()> {
+    let items = issues_for_date(source, cache, date, offline).await?;
+    if format != Format::Text {
+        return output::print_closings(format, source, &[(date, items)]);
+    }
     println!("On {}", date.format("%Y-%m-%d"));
+    let items = match items {
+        Some(items) => items,
+        None => {
+            println!("Nothing cached");
+            return Ok(());
+        }
+    };
+    if !items.is_complete() {
+        println!("Partial result: {}", UNSETTLED);
+    }
     let opened = items.opened().collect::<Vec<_>>();
     println!("{} opened: ", opened.len());
     for i in opened {
@@ -308,418 +691,239 @@ async fn handle_date(date: chrono::NaiveDate) -> Result<()> {
     Ok(())
 }
 
-async fn handle_range(start: chrono::NaiveDate, end: chrono::NaiveDate) -> Result<()> {
-    if end >= start {
-        return Err("--start must be more recent than --end".into());
-    }
-    let mut issues = Vec::new();
-    let mut date = start;
-    loop {
-        issues.push((date, Issues::for_date(date).await?));
-        date = date.pred();
-        if date == end.pred() {
-            break;
-        }
+async fn handle_range(
+    source: &dyn IssueSource,
+    cache: &Cache,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+    offline: bool,
+    format: Format,
+) -> Result<()> {
+    let issues = issues_for_range(source, cache, start, end, offline).await?;
+    if format != Format::Text {
+        return output::print_closings(format, source, &issues);
     }
     // TUI
     // gui::gui(issues).await?;
     let mut total: isize = 0;
+    let mut missing = 0;
+    let mut incomplete = 0;
     println!("Daily changes:");
     for (d, i) in issues {
-        let diff = i.diff();
-        total += diff;
-        println!("{}: {}", d.format(" %Y-%m-%d"), diff);
-    }
-    println!("Total Change: {}", total);
-    Ok(())
-}
-
-type Result<T> = std::result::Result<T, Error>;
-
-#[derive(Debug)]
-enum IssueOrEvent {
-    Issue(Issue),
-    Event(Event),
-}
-
-impl IssueOrEvent {
-    fn issue(&self) -> &Issue {
-        match self {
-            Self::Issue(i) => i,
-            Self::Event(e) => &e.issue,
-        }
-    }
-
-    fn state_change(&self) -> StateChange {
-        match self {
-            Self::Issue(_) => StateChange::Opened,
-            Self::Event(e) => match &e.id {
-                EventId::Closed => StateChange::Closed,
-                EventId::Reopened => StateChange::Opened,
-                _ => panic!("Invalid event"),
-            },
+        match i {
+            Some(i) => {
+                let diff = i.diff();
+                total += diff;
+                if i.is_complete() {
+                    println!("{}: {}", d.format(" %Y-%m-%d"), diff);
+                } else {
+                    incomplete += 1;
+                    println!("{}: {} (partial)", d.format(" %Y-%m-%d"), diff);
+                }
+            }
+            None => {
+                missing += 1;
+                println!("{}: not cached", d.format(" %Y-%m-%d"));
+            }
         }
     }
-}
-
-#[derive(Debug)]
-enum StateChange {
-    Opened,
-    Closed,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Event {
-    actor: Actor,
-    #[serde(rename = "event")]
-    id: EventId,
-    issue: Issue,
-    #[serde(rename = "created_at")]
-    when: chrono::DateTime<chrono::Utc>,
-}
-
-impl Event {
-    fn is_pull_request(&self) -> bool {
-        self.issue.is_pull_request()
-    }
-}
-
-trait Dated {
-    fn date(&self) -> chrono::NaiveDate;
-    fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool;
-}
-
-trait Paged {
-    const ESTIMATED_PAGES_PER_DAY: u32;
-    // Given a date, provide the best guess as to which page to start looking at
-    fn page_for_date(date: chrono::NaiveDate) -> u32;
-}
-
-impl Dated for Event {
-    fn date(&self) -> chrono::NaiveDate {
-        self.when.date().naive_utc()
+    println!("Total Change: {}", total);
+    if missing > 0 {
+        println!("Partial result: {} dates not cached", missing);
     }
-
-    fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool {
-        !matches!(self.id, EventId::Unknown) && &self.date() == date
+    if incomplete > 0 {
+        println!("(partial): {}", UNSETTLED);
     }
+    Ok(())
 }
 
-impl Paged for Event {
-    const ESTIMATED_PAGES_PER_DAY: u32 = 4;
-    fn page_for_date(date: chrono::NaiveDate) -> u32 {
-        let days_away = (chrono::Utc::today().naive_utc() - date).num_days();
-        if days_away > 1 {
-            let answer = (days_away as u32) * Self::ESTIMATED_PAGES_PER_DAY;
-            debug!(
-                "Selected event page {} based on number of days away ({}) * estimated pages per day ({})",
-                answer,
-                days_away,
-                Self::ESTIMATED_PAGES_PER_DAY
-            );
-            answer
-        } else if days_away == 1 {
-            use chrono::Timelike;
-            // TODO: this will change during the day and needs to be adjusted
-            debug!(
-                "Selected event page 4 since date is yesterday: {} hours away",
-                chrono::Utc::now().time().hour()
-            );
-            4
-        } else {
-            debug!("Selected event page 0 since date is today");
-            0
+/// Writing the weekly triage report
+struct Report<'a> {
+    forge: Forge,
+    source: &'a dyn IssueSource,
+    /// Where opened and closed issues are looked up
+    closings_source: &'a dyn IssueSource,
+    cache: &'a Cache,
+    offline: bool,
+}
+
+impl Report<'_> {
+    /// Look up the closings and stale issues selected by `command` and print the report
+    async fn run(&self, command: ReportCommand, mut query: TriageQuery) -> Result<()> {
+        let start = command
+            .start
+            .unwrap_or_else(|| chrono::Utc::today().naive_utc().pred());
+        let end = command
+            .end
+            .unwrap_or_else(|| start - chrono::Duration::days(6));
+        let days =
+            issues_for_range(self.closings_source, self.cache, start, end, self.offline).await?;
+        if let Some(since) = command.since {
+            query = query.since(since);
         }
+        let triage = query.run(self.source, self.cache).await?;
+        if let Some(limit) = triage.rate_limited {
+            eprintln!("Error: hit {} {}. Stop early", self.forge, limit);
+        }
+        let options = report::ReportOptions {
+            labels: &command.tags,
+            group_by: command.group_by.as_deref(),
+            top: command.top,
+        };
+        report::print_report(self.closings_source, &days, &triage, &options)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Issue {
-    number: u32,
-    title: String,
-    comments: u32,
-    pull_request: Option<PullRequest>,
-    created_at: chrono::DateTime<chrono::Utc>,
-}
-
-impl Issue {
-    fn is_pull_request(&self) -> bool {
-        self.pull_request.is_some()
-    }
-}
-
-impl Dated for Issue {
-    fn date(&self) -> chrono::NaiveDate {
-        self.created_at.date().naive_utc()
-    }
-
-    fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool {
-        &self.date() == date
+async fn handle_backfill(
+    forge: Forge,
+    source: &dyn IssueSource,
+    closings_source: &dyn IssueSource,
+    cache: &Cache,
+    command: BackfillCommand,
+) -> Result<()> {
+    let mut backfill = Backfill::new(command.from, command.to)?;
+    if backfill.to() < command.to {
+        eprintln!(
+            "Warning: stopping at today ({} in UTC) instead of {}",
+            backfill.to(),
+            command.to
+        );
     }
-}
-
-impl Paged for Issue {
-    const ESTIMATED_PAGES_PER_DAY: u32 = 1;
-    fn page_for_date(date: chrono::NaiveDate) -> u32 {
-        let days_away = (chrono::Utc::today().naive_utc() - date).num_days();
-        (days_away as u32) / 6
+    if command.triage {
+        let query = TriageQuery::new()
+            .labels(&command.labels)
+            .since(command.from);
+        backfill = backfill.triage(query);
     }
-}
-
-impl std::fmt::Display for Issue {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("#{}: {}", self.number, self.title))
+    let report = backfill
+        .run(source, closings_source, cache, |date| {
+            println!("Fetched {}", date)
+        })
+        .await?;
+    for (date, e) in &report.failed {
+        eprintln!("Warning: failed to fetch {}: {}", date, e);
     }
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Comment {
-    body: String,
-    created_at: chrono::DateTime<chrono::Utc>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct PullRequest {}
-
-#[derive(Serialize, Deserialize, Debug)]
-enum EventId {
-    #[serde(rename = "closed")]
-    Closed,
-    #[serde(rename = "reopened")]
-    Reopened,
-    #[serde(other)]
-    Unknown,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Actor {
-    login: String,
-}
-
-struct Issues {
-    items: Vec<IssueOrEvent>,
-}
-
-impl Issues {
-    async fn for_date(date: chrono::NaiveDate) -> Result<Self> {
-        let (events, issues) = tokio::join!(events_for_date(date), issues_for_date(date));
-        let events = events?;
-        let issues = issues?;
-        let mut items = Vec::with_capacity(events.len() + issues.len());
-        items.extend(
-            events
-                .into_iter()
-                .filter(|i| !i.is_pull_request())
-                .map(IssueOrEvent::Event),
+    // Describe rate limits along with the forge imposing them
+    let describe = |error: Error| match error {
+        Error::RateLimited(limit) => format!("hit {} {}", forge, limit),
+        e => e.to_string(),
+    };
+    if let Some((date, error)) = report.stopped_at {
+        println!(
+            "{} dates fetched, {} already cached",
+            report.fetched, report.cached
         );
-        items.extend(
-            issues
-                .into_iter()
-                .filter(|i| !i.is_pull_request())
-                .map(IssueOrEvent::Issue),
+        eprintln!(
+            "Stopped at {}: {}. Run again to continue from there",
+            date,
+            describe(error)
         );
-        items.sort_by(|i1, i2| i1.issue().number.cmp(&i2.issue().number));
-        items.dedup_by(|i1, i2| i1.issue().number == i2.issue().number);
-        Ok(Self { items })
-    }
-
-    fn opened(&self) -> impl Iterator<Item = &Issue> {
-        self.items
-            .iter()
-            .filter(|i| matches!(i.state_change(), StateChange::Opened))
-            .map(|i| i.issue())
+        return Ok(());
     }
-
-    fn closed(&self) -> impl Iterator<Item = &Issue> {
-        self.items
-            .iter()
-            .filter(|i| matches!(i.state_change(), StateChange::Closed))
-            .map(|i| i.issue())
-    }
-
-    #[allow(dead_code)]
-    fn diff(&self) -> isize {
-        let opened = self.opened().count() as isize;
-        let closed = self.closed().count() as isize;
-        opened - closed
+    println!(
+        "{} dates fetched, {} already cached, {} failed",
+        report.fetched,
+        report.cached,
+        report.failed.len()
+    );
+    match report.triage_stopped {
+        Some(error) => eprintln!(
+            "Stopped checking the activity of open issues: {}. Run again to continue",
+            describe(error)
+        ),
+        None if command.triage => println!("Checked the activity of open issues"),
+        None => {}
     }
+    Ok(())
 }
 
-async fn events_for_date(date: chrono::NaiveDate) -> Result<Vec<Event>> {
-    let es = match read_cache(&date, CacheType::Events).await? {
-        Some(es) => es,
-        None => {
-            let events = fetch_events_for_date(date).await?;
-            let _ = write_cache(&date, &events, CacheType::Events).await;
-            events
+fn handle_cache(command: CacheCommand, dir: &Path, repo: &Repo) -> Result<()> {
+    let cache = Cache::open(dir)?;
+    import_legacy(&cache, repo);
+    match command {
+        CacheCommand::List => {
+            let lists = cache.lists()?;
+            if lists.is_empty() {
+                println!("No dates cached in '{}'", dir.display());
+            }
+            for list in lists {
+                let status = match (list.complete, list.fetched_at) {
+                    (true, _) => "complete".to_owned(),
+                    (false, Some(fetched_at)) => format!(
+                        "incomplete (fetched {})",
+                        fetched_at.format("%Y-%m-%d %H:%M UTC")
+                    ),
+                    (false, None) => "incomplete".to_owned(),
+                };
+                println!("{}  {:<13}  {}", list.date, list.kind.to_string(), status);
+            }
         }
-    };
-    Ok(es)
-}
-
-async fn issues_for_date(date: chrono::NaiveDate) -> Result<Vec<Issue>> {
-    let es = match read_cache(&date, CacheType::Issues).await? {
-        Some(es) => es,
-        None => {
-            let issues = fetch_issues_for_date(date).await?;
-            let _ = write_cache(&date, &issues, CacheType::Issues).await;
-            issues
+        CacheCommand::Stats => {
+            let stats = cache.stats()?;
+            println!("Cache in '{}' ({})", dir.display(), format_size(stats.size));
+            println!("Issues: {}", stats.issues);
+            println!("Events: {}", stats.events);
+            println!("Comments: {}", stats.comments);
+            println!(
+                "Dates: {} complete, {} incomplete",
+                stats.complete_lists, stats.incomplete_lists
+            );
+            println!(
+                "Triage: {} fresh, {} stale",
+                stats.fresh_triage, stats.stale_triage
+            );
         }
-    };
-    Ok(es)
-}
-
-#[derive(Clone, Copy, Debug)]
-enum CacheType {
-    Issues,
-    Events,
-}
-
-impl std::fmt::Display for CacheType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let typ = match self {
-            CacheType::Issues => "issues",
-            CacheType::Events => "events",
-        };
-        f.write_str(typ)
-    }
-}
-
-async fn read_cache<T: serde::de::DeserializeOwned>(
-    date: &chrono::NaiveDate,
-    cache_type: CacheType,
-) -> Result<Option<Vec<T>>> {
-    let path = cache_path(date, cache_type);
-    debug!("Trying to read from '{}' cache @ '{}'", cache_type, path);
-    let result = tokio::fs::read(&path).await;
-    if let Err(std::io::ErrorKind::NotFound) = result.as_ref().map_err(|e| e.kind()) {
-        debug!("'{}' not in cache", path);
-        return Ok(None);
-    }
-    let result = result?;
-
-    let es = match serde_json::from_slice(&result) {
-        Ok(es) => Some(es),
-        Err(_) => {
-            debug!("Failed to parse cache for '{}' as JSON. Deleteing...", date);
-            let _ = tokio::fs::remove_file(&path).await;
-            None
+        CacheCommand::Prune { older_than } => {
+            let removed = cache.prune(older_than)?;
+            println!("Removed {} entries older than {}", removed, older_than);
         }
-    };
-    Ok(es)
-}
-
-async fn write_cache<T: Serialize>(
-    date: &chrono::NaiveDate,
-    events: &Vec<T>,
-    cache_type: CacheType,
-) -> Result<()> {
-    let path = cache_path(date, cache_type);
-    debug!("Writing to cache: '{}'", path);
-    let events = serde_json::to_vec(&events)?;
-    Ok(tokio::fs::write(&path, &events).await?)
-}
-
-fn cache_path(date: &chrono::NaiveDate, cache_type: CacheType) -> String {
-    format!("database/{}-{}.json", date.format("%Y-%m-%d"), cache_type)
-}
-
-async fn fetch_issues_for_date(date: chrono::NaiveDate) -> Result<Vec<Issue>> {
-    fetch_for_date(date, |page| {
-        github::fetch_issue_page(
-            page,
-            100,
-            &[],
-            github::SortedBy::Created,
-            github::Direction::NewestFirst,
-        )
-    })
-    .await
-}
-
-async fn fetch_events_for_date(date: chrono::NaiveDate) -> Result<Vec<Event>> {
-    fetch_for_date(date, |page| github::fetch_event_page(page, 100)).await
-}
-
-async fn fetch_for_date<T, F, Fut>(date: chrono::NaiveDate, fetch: F) -> Result<Vec<T>>
-where
-    T: Dated + Paged,
-    F: Fn(u32) -> Fut,
-    Fut: std::future::Future<Output = Result<Vec<T>>>,
-{
-    let today = chrono::Utc::today().naive_utc();
-    let days_away = (today - date).num_days();
-    assert!(days_away >= 0);
-    let mut page_number = T::page_for_date(date.clone());
-    let mut items = Vec::new();
-    let mut fetch_index = 0;
-    let mut pages_per_day = T::ESTIMATED_PAGES_PER_DAY;
-    loop {
-        let page = fetch(page_number).await?;
-        if page.is_empty() {
-            break;
+        CacheCommand::Clear { date, issue } if date.is_empty() && issue.is_empty() => {
+            cache.clear()?;
+            println!("Cleared the cache in '{}'", dir.display());
         }
-        let page_length = page.len();
-        let first = page.iter().position(|e| e.date() == date);
-        let last = page.iter().rev().position(|e| e.date() == date);
-        let range = first.zip(last).map(|(f, l)| f..(page_length - 1 - l));
-        match range {
-            Some(r) => {
-                // At least some items were for this date
-                debug!("At least some items found in range '{:?}", r);
-                let i = page.into_iter().filter(|e| e.is_relevant_for_date(&date));
-                items.extend(i);
-                if r.start != 0 && r.end != (page_length - 1) {
-                    // The page contained all items for the date
-                    debug!("All items for '{:?}' contained in page. Breaking...", date);
-                    break;
-                } else if r.start == 0 && fetch_index == 0 {
-                    // TODO: we throw away results here that we could keep
-                    debug!("In the middle of the day. Going back 1 page...");
-                    page_number -= 1;
-                } else if r.end != (page_length - 1) {
-                    debug!("We reached the end of the date");
-                    break;
-                } else {
-                    debug!("Date '{:?}' spans beyond page", date);
-                    page_number += 1;
-                    fetch_index += 1;
-                }
+        CacheCommand::Clear { date, issue } => {
+            for date in date {
+                let removed = cache.clear_date(date)?;
+                println!("Removed {} lists for {}", removed, date);
             }
-            None => {
-                // No items in this page matched the date
-                let most_recent = &page[0].date();
-                let least_recent = &page[page_length - 1].date();
-                debug!(
-                    "No items for target date ({:?}) contained in page {} which spans from {:?} to {:?}",
-                    date, page_number, least_recent, most_recent
-                );
-                if least_recent > &date {
-                    debug!("The least recent item in page is further in future ({:?}) than the target date ({:?}). Going back in time...", least_recent, date);
-                    let diff = (*least_recent - date).num_days() as u32;
-                    let pages = diff * pages_per_day;
-                    debug!(
-                        "{} days in future... going back in time +{} pages",
-                        diff, pages
-                    );
-                    page_number += pages;
-                } else {
-                    debug!("The most recent item in page is further in past ({:?}) than the target date ({:?}). Going forward in time...", most_recent, date);
-                    let diff = (date - *most_recent).num_days() as u32;
-                    let pages = diff * pages_per_day;
-                    debug!(
-                        "{} days in past... moving forward in time -{} pages",
-                        diff, pages
-                    );
-                    page_number = page_number
-                        .checked_sub(pages)
-                        .unwrap_or_else(|| page_number - 1)
+            for issue in issue {
+                if !cache.clear_activity(issue)? {
+                    eprintln!("Warning: no activity cached for #{}", issue);
                 }
-                // Decrease the pages per day estimate to avoid swinging back and forth
-                pages_per_day = pages_per_day.checked_add(1).unwrap_or(0);
             }
         }
+        CacheCommand::Verify => {
+            let problems = cache.verify()?;
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                return Err(format!("found {} problems in the cache", problems.len()).into());
+            }
+            println!("No problems found");
+        }
+        CacheCommand::Export => {
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            cache.export(&mut out)?;
+            writeln!(out)?;
+        }
     }
+    Ok(())
+}
 
-    Ok(items)
+/// A size in bytes for humans
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
 }
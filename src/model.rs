@@ -0,0 +1,139 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Something that happened to an issue, like it being closed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Event {
+    /// Who caused the event, if known
+    pub actor: Option<Actor>,
+    /// What happened
+    #[serde(rename = "event")]
+    pub id: EventId,
+    /// The issue it happened to
+    pub issue: Issue,
+    /// When it happened
+    #[serde(rename = "created_at")]
+    pub when: chrono::DateTime<chrono::Utc>,
+}
+
+impl Event {
+    /// Whether the event happened to a pull request rather than an issue
+    pub fn is_pull_request(&self) -> bool {
+        self.issue.is_pull_request()
+    }
+}
+
+pub(crate) trait Dated {
+    fn date(&self) -> chrono::NaiveDate;
+    fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool;
+}
+
+impl Dated for Event {
+    fn date(&self) -> chrono::NaiveDate {
+        self.when.date().naive_utc()
+    }
+
+    fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool {
+        !matches!(self.id, EventId::Unknown) && &self.date() == date
+    }
+}
+
+/// An issue or pull request
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Issue {
+    /// The issue's number within the repository
+    pub number: u32,
+    /// The issue's title
+    pub title: String,
+    /// The number of comments
+    pub comments: u32,
+    /// Set if this is a pull request rather than an issue
+    pub pull_request: Option<PullRequest>,
+    /// The names of the issue's labels
+    #[serde(default, deserialize_with = "label_names")]
+    pub labels: Vec<String>,
+    /// Who opened the issue, if known
+    #[serde(default, rename = "user")]
+    pub author: Option<Actor>,
+    /// When the issue was opened
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the issue was last closed, if it is closed
+    pub closed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Issue {
+    /// Whether this is a pull request rather than an issue
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+
+    /// Whether the issue has all of `labels`
+    pub fn has_labels(&self, labels: &[String]) -> bool {
+        labels.iter().all(|l| self.labels.contains(l))
+    }
+}
+
+/// Read labels given either by name, like GitLab does, or as objects with a `name`
+fn label_names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Label {
+        Name(String),
+        Object { name: String },
+    }
+    Ok(Vec::<Label>::deserialize(deserializer)?
+        .into_iter()
+        .map(|label| match label {
+            Label::Name(name) | Label::Object { name } => name,
+        })
+        .collect())
+}
+
+impl Dated for Issue {
+    fn date(&self) -> chrono::NaiveDate {
+        self.created_at.date().naive_utc()
+    }
+
+    fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool {
+        &self.date() == date
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("#{}: {}", self.number, self.title))
+    }
+}
+
+/// A comment on an issue
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Comment {
+    /// The comment's Markdown text
+    pub body: String,
+    /// When the comment was made
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Marker for issues that are pull requests
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PullRequest {}
+
+/// The kind of an `Event`
+#[derive(Serialize, Deserialize, Debug)]
+pub enum EventId {
+    /// The issue was closed
+    #[serde(rename = "closed")]
+    Closed,
+    /// The issue was reopened
+    #[serde(rename = "reopened")]
+    Reopened,
+    /// Anything else, like labelling or assigning the issue
+    #[serde(other)]
+    Unknown,
+}
+
+/// The user causing an `Event` or opening an `Issue`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Actor {
+    /// The user's name
+    pub login: String,
+}
@@ -0,0 +1,866 @@
+//! Printing results in the machine-readable formats selected with `--format`
+//!
+//! The shape of the output is documented in the README and kept stable: fields
+//! may be added, but not renamed or removed.
+
+use std::io::Write;
+
+use serde::Serialize;
+use triage_tracker::{Activity, Issue, IssueSource, Issues, Result, TriageReport};
+
+use crate::chart;
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// Free text for humans
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma separated values with a header line
+    Csv,
+    /// A Markdown table
+    Markdown,
+    /// A static HTML page with charts
+    Html,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(format!(
+                "unknown format '{}': expected `text`, `json`, `ndjson`, `csv`, `markdown` or `html`",
+                s
+            )),
+        }
+    }
+}
+
+/// The issues opened and closed on a day
+#[derive(Debug, Serialize)]
+struct Day {
+    date: chrono::NaiveDate,
+    status: Status,
+    /// The counts are `None` for missing days
+    opened: Option<usize>,
+    closed: Option<usize>,
+    net: Option<isize>,
+    opened_issues: Vec<IssueSummary>,
+    closed_issues: Vec<IssueSummary>,
+}
+
+/// How much of an answer is known
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Complete,
+    /// A day looked up before it settled, or open issues whose activity isn't cached
+    Partial,
+    /// Not cached when running offline
+    Missing,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Complete => "complete",
+            Self::Partial => "partial",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct IssueSummary {
+    number: u32,
+    title: String,
+    url: String,
+}
+
+impl IssueSummary {
+    fn new(source: &dyn IssueSource, issue: &Issue) -> Self {
+        Self {
+            number: issue.number,
+            title: issue.title.clone(),
+            url: source.issue_url(issue.number),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Totals {
+    opened: usize,
+    closed: usize,
+    net: isize,
+}
+
+#[derive(Debug, Serialize)]
+struct ClosingsReport<'a> {
+    repo: String,
+    days: &'a [Day],
+    total: Totals,
+}
+
+impl Day {
+    fn new(source: &dyn IssueSource, date: chrono::NaiveDate, issues: Option<&Issues>) -> Self {
+        let summarize = |issue| IssueSummary::new(source, issue);
+        match issues {
+            Some(issues) => Self {
+                date,
+                status: if issues.is_complete() {
+                    Status::Complete
+                } else {
+                    Status::Partial
+                },
+                opened: Some(issues.opened().count()),
+                closed: Some(issues.closed().count()),
+                net: Some(issues.diff()),
+                opened_issues: issues.opened().map(summarize).collect(),
+                closed_issues: issues.closed().map(summarize).collect(),
+            },
+            None => Self {
+                date,
+                status: Status::Missing,
+                opened: None,
+                closed: None,
+                net: None,
+                opened_issues: Vec::new(),
+                closed_issues: Vec::new(),
+            },
+        }
+    }
+}
+
+/// The size of the charts in HTML pages, in pixels
+const HTML_CHART_SIZE: (u32, u32) = (800, 320);
+
+/// Print the closings of `issues` in a machine-readable `format`
+pub(crate) fn print_closings(
+    format: Format,
+    source: &dyn IssueSource,
+    issues: &[(chrono::NaiveDate, Option<Issues>)],
+) -> Result<()> {
+    let stdout = std::io::stdout();
+    write_closings(&mut stdout.lock(), format, source, issues)
+}
+
+/// Write the closings of `issues` to `out` in a machine-readable `format`
+fn write_closings(
+    out: &mut impl Write,
+    format: Format,
+    source: &dyn IssueSource,
+    issues: &[(chrono::NaiveDate, Option<Issues>)],
+) -> Result<()> {
+    let days = issues
+        .iter()
+        .map(|(date, issues)| Day::new(source, *date, issues.as_ref()))
+        .collect::<Vec<_>>();
+    match format {
+        Format::Text => unreachable!("text is printed by the commands themselves"),
+        Format::Markdown => {
+            return Err("markdown output is only available for `triaged`".into());
+        }
+        Format::Html => write_closings_html(out, source, issues, &days)?,
+        Format::Json => {
+            let mut total = Totals::default();
+            for day in &days {
+                total.opened += day.opened.unwrap_or(0);
+                total.closed += day.closed.unwrap_or(0);
+                total.net += day.net.unwrap_or(0);
+            }
+            let report = ClosingsReport {
+                repo: source.repo().to_string(),
+                days: &days,
+                total,
+            };
+            serde_json::to_writer_pretty(&mut *out, &report)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for day in &days {
+                serde_json::to_writer(&mut *out, day)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv => {
+            writeln!(
+                out,
+                "date,status,opened,closed,net,opened_issues,closed_issues"
+            )?;
+            for day in &days {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{}",
+                    day.date,
+                    day.status.as_str(),
+                    csv_count(day.opened),
+                    csv_count(day.closed),
+                    csv_count(day.net),
+                    issue_numbers(&day.opened_issues),
+                    issue_numbers(&day.closed_issues),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write a standalone HTML page with charts and a table of `days`
+fn write_closings_html(
+    out: &mut impl Write,
+    source: &dyn IssueSource,
+    issues: &[(chrono::NaiveDate, Option<Issues>)],
+    days: &[Day],
+) -> Result<()> {
+    let closings = chart::Closings::new(issues);
+    let daily = chart::to_svg(HTML_CHART_SIZE, |area| {
+        chart::draw(
+            area,
+            &closings,
+            chart::Series::Daily,
+            chart::Style::Bar,
+            "Opened and closed issues per day",
+        )
+    })?;
+    let cumulative = chart::to_svg(HTML_CHART_SIZE, |area| {
+        chart::draw(
+            area,
+            &closings,
+            chart::Series::Cumulative,
+            chart::Style::Line,
+            "Change in open issues",
+        )
+    })?;
+    let mut days = days.iter().collect::<Vec<_>>();
+    days.sort_by_key(|day| day.date);
+    let mut total = Totals::default();
+    for day in &days {
+        total.opened += day.opened.unwrap_or(0);
+        total.closed += day.closed.unwrap_or(0);
+        total.net += day.net.unwrap_or(0);
+    }
+    let repo = html_escape(&source.repo().to_string());
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Net closings for {}</title>", repo)?;
+    writeln!(out, "<style>")?;
+    writeln!(
+        out,
+        "body {{ font-family: sans-serif; max-width: 820px; margin: 2em auto; }}"
+    )?;
+    writeln!(out, "table {{ border-collapse: collapse; }}")?;
+    writeln!(
+        out,
+        "th, td {{ padding: 0.2em 0.8em; border-bottom: 1px solid #ddd; text-align: right; }}"
+    )?;
+    writeln!(
+        out,
+        "th:first-child, td:first-child, td:last-child {{ text-align: left; }}"
+    )?;
+    writeln!(out, "</style>")?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>Net closings for {}</h1>", repo)?;
+    if let (Some(first), Some(last)) = (days.first(), days.last()) {
+        writeln!(
+            out,
+            "<p>From {} to {}: {} opened, {} closed, net change {}.</p>",
+            first.date, last.date, total.opened, total.closed, total.net
+        )?;
+    }
+    let incomplete = days
+        .iter()
+        .filter(|day| !matches!(day.status, Status::Complete))
+        .count();
+    if incomplete > 0 {
+        writeln!(
+            out,
+            "<p>{} of the dates weren't cached or were fetched before they settled (within 24 hours of their end), so the totals are incomplete.</p>",
+            incomplete
+        )?;
+    }
+    writeln!(out, "{}", daily)?;
+    writeln!(out, "{}", cumulative)?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Date</th><th>Opened</th><th>Closed</th><th>Net</th><th>Status</th></tr>"
+    )?;
+    for day in days {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            day.date,
+            csv_count(day.opened),
+            csv_count(day.closed),
+            csv_count(day.net),
+            day.status.as_str()
+        )?;
+    }
+    writeln!(
+        out,
+        "<tr><th>Total</th><th>{}</th><th>{}</th><th>{}</th><th></th></tr>",
+        total.opened, total.closed, total.net
+    )?;
+    writeln!(out, "</table>")?;
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+    Ok(())
+}
+
+/// An untriaged issue
+#[derive(Debug, Serialize)]
+struct TriagedIssue {
+    number: u32,
+    title: String,
+    url: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    comments: u32,
+    labels: Vec<String>,
+    /// `no-comments`, `last-commented` or `no-activity-since`
+    activity: &'static str,
+    /// The date of the last comment, or the date without activity since; `None` without comments
+    last_activity: Option<chrono::NaiveDate>,
+    /// Whether the activity was known from the cache rather than fetched
+    from_cache: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct TriagedReport<'a> {
+    repo: String,
+    since: chrono::NaiveDate,
+    /// `missing` with `--offline` if no open issues are cached, `partial` if
+    /// some of their activity isn't
+    status: Status,
+    issues: &'a [TriagedIssue],
+    /// With `--offline`, the open issues whose activity isn't cached
+    missing: Vec<IssueSummary>,
+    /// Whether a rate limit stopped the query before all issues were checked
+    rate_limited: bool,
+}
+
+/// Print the result of a triage query in a machine-readable `format`
+pub(crate) fn print_triaged(
+    format: Format,
+    source: &dyn IssueSource,
+    report: &TriageReport,
+) -> Result<()> {
+    let stdout = std::io::stdout();
+    write_triaged(&mut stdout.lock(), format, source, report)
+}
+
+/// Write the result of a triage query to `out` in a machine-readable `format`
+fn write_triaged(
+    out: &mut impl Write,
+    format: Format,
+    source: &dyn IssueSource,
+    report: &TriageReport,
+) -> Result<()> {
+    let issues = report
+        .untriaged
+        .iter()
+        .map(|untriaged| {
+            let issue = &untriaged.issue;
+            let (activity, last_activity) = match untriaged.activity {
+                None => ("no-comments", None),
+                Some(Activity::LastCommented(date)) => ("last-commented", Some(date)),
+                Some(Activity::NoActivitySince(date)) => ("no-activity-since", Some(date)),
+            };
+            TriagedIssue {
+                number: issue.number,
+                title: issue.title.clone(),
+                url: source.issue_url(issue.number),
+                created_at: issue.created_at,
+                comments: issue.comments,
+                labels: issue.labels.clone(),
+                activity,
+                last_activity,
+                from_cache: untriaged.from_cache,
+            }
+        })
+        .collect::<Vec<_>>();
+    match format {
+        Format::Text => unreachable!("text is printed by the commands themselves"),
+        Format::Html => {
+            return Err("html output is only available for `closings`".into());
+        }
+        Format::Json => {
+            let report = TriagedReport {
+                repo: source.repo().to_string(),
+                since: report.since,
+                status: if report.open_issues_missing {
+                    Status::Missing
+                } else if !report.missing.is_empty() {
+                    Status::Partial
+                } else {
+                    Status::Complete
+                },
+                issues: &issues,
+                missing: report
+                    .missing
+                    .iter()
+                    .map(|issue| IssueSummary::new(source, issue))
+                    .collect(),
+                rate_limited: report.rate_limited.is_some(),
+            };
+            serde_json::to_writer_pretty(&mut *out, &report)?;
+            writeln!(out)?;
+        }
+        // Without a place for the report's status, a cache miss can only be an error
+        Format::Ndjson | Format::Csv if report.open_issues_missing => {
+            return Err("no open issues are cached, so none were checked".into());
+        }
+        Format::Ndjson => {
+            for issue in &issues {
+                serde_json::to_writer(&mut *out, issue)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv => {
+            writeln!(
+                out,
+                "number,title,url,created_at,comments,labels,activity,last_activity,from_cache"
+            )?;
+            for issue in &issues {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{}",
+                    issue.number,
+                    csv_field(&issue.title),
+                    csv_field(&issue.url),
+                    issue.created_at.format("%Y-%m-%d"),
+                    issue.comments,
+                    csv_field(&issue.labels.join(";")),
+                    issue.activity,
+                    csv_count(issue.last_activity),
+                    issue.from_cache,
+                )?;
+            }
+        }
+        Format::Markdown => {
+            if report.open_issues_missing {
+                writeln!(
+                    out,
+                    "No open issues of {} are cached, so none were checked.",
+                    source.repo()
+                )?;
+                return Ok(());
+            }
+            writeln!(
+                out,
+                "{} untriaged issue{} in {} without activity since {}:",
+                issues.len(),
+                if issues.len() != 1 { "s" } else { "" },
+                source.repo(),
+                report.since
+            )?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "| Issue | Title | Created | Comments | Last activity | Labels |"
+            )?;
+            writeln!(out, "|---|---|---|---:|---|---|")?;
+            for issue in &issues {
+                let activity = match (issue.activity, issue.last_activity) {
+                    ("last-commented", Some(date)) => format!("commented {}", date),
+                    (_, Some(date)) => format!("none since {}", date),
+                    _ => "no comments".to_owned(),
+                };
+                writeln!(
+                    out,
+                    "| [#{}]({}) | {} | {} | {} | {}{} | {} |",
+                    issue.number,
+                    issue.url,
+                    markdown_escape(&issue.title),
+                    issue.created_at.format("%Y-%m-%d"),
+                    issue.comments,
+                    activity,
+                    if issue.from_cache { " (cached)" } else { "" },
+                    issue
+                        .labels
+                        .iter()
+                        .map(|l| format!("`{}`", l.replace('`', "'")))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+            }
+            if !report.missing.is_empty() {
+                writeln!(out)?;
+                writeln!(
+                    out,
+                    "The activity of these issues isn't cached, so they may be untriaged too:"
+                )?;
+                writeln!(out)?;
+                for issue in &report.missing {
+                    writeln!(
+                        out,
+                        "* [#{}]({}) {}",
+                        issue.number,
+                        source.issue_url(issue.number),
+                        markdown_escape(&issue.title)
+                    )?;
+                }
+            }
+            if report.rate_limited.is_some() {
+                writeln!(out)?;
+                writeln!(out, "Hit a rate limit before all issues were checked.")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A field for CSV, quoted if needed
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Text for a Markdown table cell
+pub(crate) fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '|' | '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Text for HTML
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A count for CSV, empty if unknown
+fn csv_count<T: ToString>(count: Option<T>) -> String {
+    count.map(|c| c.to_string()).unwrap_or_default()
+}
+
+/// Issue numbers separated by spaces, which need no quoting in CSV
+fn issue_numbers(issues: &[IssueSummary]) -> String {
+    issues
+        .iter()
+        .map(|i| i.number.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use triage_tracker::{Comment, Event, EventId, Page, Repo, UntriagedIssue};
+
+    use super::*;
+
+    /// A title that needs quoting in CSV and escaping in Markdown
+    const AWKWARD_TITLE: &str = "Crash on \"a, b\"\nwith | in *it*";
+
+    /// A source with a single day of issues, 2021-06-05
+    struct Fixed(Repo);
+
+    #[async_trait]
+    impl IssueSource for Fixed {
+        fn repo(&self) -> &Repo {
+            &self.0
+        }
+
+        fn issue_url(&self, number: u32) -> String {
+            format!("https://forge.example/{}/issues/{}", self.0, number)
+        }
+
+        async fn issues_created_on(&self, date: chrono::NaiveDate) -> Result<Vec<Issue>> {
+            assert_eq!(date, day());
+            Ok(vec![
+                issue(1, AWKWARD_TITLE, "2021-06-05T10:00:00Z"),
+                issue(2, "Plain", "2021-06-05T11:00:00Z"),
+            ])
+        }
+
+        async fn state_changes_on(&self, date: chrono::NaiveDate) -> Result<Vec<Event>> {
+            assert_eq!(date, day());
+            Ok(vec![Event {
+                actor: None,
+                id: EventId::Closed,
+                issue: issue(3, "Closed", "2021-06-01T10:00:00Z"),
+                when: "2021-06-05T12:00:00Z".parse().unwrap(),
+            }])
+        }
+
+        async fn open_issues(&self, _: &[String], _: u32) -> Result<Page<Issue>> {
+            Err("not needed".into())
+        }
+
+        async fn comments_since(&self, _: u32, _: chrono::NaiveDate) -> Result<Vec<Comment>> {
+            Err("not needed".into())
+        }
+    }
+
+    fn day() -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd(2021, 6, 5)
+    }
+
+    fn issue(number: u32, title: &str, created_at: &str) -> Issue {
+        Issue {
+            number,
+            title: title.to_owned(),
+            comments: number,
+            pull_request: None,
+            labels: Vec::new(),
+            author: None,
+            created_at: created_at.parse().unwrap(),
+            closed_at: None,
+        }
+    }
+
+    fn source() -> Fixed {
+        Fixed("owner/repo".parse().unwrap())
+    }
+
+    /// The closings of a looked up day, followed by a day that wasn't cached
+    async fn closings(format: Format) -> String {
+        let source = source();
+        let cache = triage_tracker::Cache::in_memory().unwrap();
+        let issues = Issues::for_date(&source, &cache, day()).await.unwrap();
+        let days = [(day(), Some(issues)), (day().succ(), None)];
+        let mut out = Vec::new();
+        write_closings(&mut out, format, &source, &days).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn triage_report() -> TriageReport {
+        let mut labelled = issue(1, AWKWARD_TITLE, "2021-05-01T10:00:00Z");
+        labelled.labels = vec!["bug".to_owned(), "needs `info`".to_owned()];
+        TriageReport {
+            since: chrono::NaiveDate::from_ymd(2021, 6, 1),
+            untriaged: vec![
+                UntriagedIssue {
+                    issue: labelled,
+                    activity: Some(Activity::LastCommented(chrono::NaiveDate::from_ymd(
+                        2021, 5, 20,
+                    ))),
+                    from_cache: false,
+                },
+                UntriagedIssue {
+                    issue: issue(4, "Quiet", "2021-05-02T10:00:00Z"),
+                    activity: None,
+                    from_cache: true,
+                },
+            ],
+            rate_limited: None,
+            missing: vec![issue(5, "Not | cached", "2021-05-03T10:00:00Z")],
+            open_issues_missing: false,
+        }
+    }
+
+    fn triaged(format: Format, report: &TriageReport) -> String {
+        let mut out = Vec::new();
+        write_triaged(&mut out, format, &source(), report).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn closings_as_json() {
+        let expected = r#"{
+  "repo": "owner/repo",
+  "days": [
+    {
+      "date": "2021-06-05",
+      "status": "complete",
+      "opened": 2,
+      "closed": 1,
+      "net": 1,
+      "opened_issues": [
+        {
+          "number": 1,
+          "title": "Crash on \"a, b\"\nwith | in *it*",
+          "url": "https://forge.example/owner/repo/issues/1"
+        },
+        {
+          "number": 2,
+          "title": "Plain",
+          "url": "https://forge.example/owner/repo/issues/2"
+        }
+      ],
+      "closed_issues": [
+        {
+          "number": 3,
+          "title": "Closed",
+          "url": "https://forge.example/owner/repo/issues/3"
+        }
+      ]
+    },
+    {
+      "date": "2021-06-06",
+      "status": "missing",
+      "opened": null,
+      "closed": null,
+      "net": null,
+      "opened_issues": [],
+      "closed_issues": []
+    }
+  ],
+  "total": {
+    "opened": 2,
+    "closed": 1,
+    "net": 1
+  }
+}
+"#;
+        assert_eq!(closings(Format::Json).await, expected);
+    }
+
+    #[tokio::test]
+    async fn closings_as_ndjson() {
+        let expected = concat!(
+            r#"{"date":"2021-06-05","status":"complete","opened":2,"closed":1,"net":1,"#,
+            r#""opened_issues":[{"number":1,"title":"Crash on \"a, b\"\nwith | in *it*","url":"https://forge.example/owner/repo/issues/1"},"#,
+            r#"{"number":2,"title":"Plain","url":"https://forge.example/owner/repo/issues/2"}],"#,
+            r#""closed_issues":[{"number":3,"title":"Closed","url":"https://forge.example/owner/repo/issues/3"}]}"#,
+            "\n",
+            r#"{"date":"2021-06-06","status":"missing","opened":null,"closed":null,"net":null,"opened_issues":[],"closed_issues":[]}"#,
+            "\n",
+        );
+        assert_eq!(closings(Format::Ndjson).await, expected);
+    }
+
+    #[tokio::test]
+    async fn closings_as_csv() {
+        let expected = "\
+date,status,opened,closed,net,opened_issues,closed_issues
+2021-06-05,complete,2,1,1,1 2,3
+2021-06-06,missing,,,,,
+";
+        assert_eq!(closings(Format::Csv).await, expected);
+    }
+
+    #[test]
+    fn triaged_as_json() {
+        let expected = r#"{
+  "repo": "owner/repo",
+  "since": "2021-06-01",
+  "status": "partial",
+  "issues": [
+    {
+      "number": 1,
+      "title": "Crash on \"a, b\"\nwith | in *it*",
+      "url": "https://forge.example/owner/repo/issues/1",
+      "created_at": "2021-05-01T10:00:00Z",
+      "comments": 1,
+      "labels": [
+        "bug",
+        "needs `info`"
+      ],
+      "activity": "last-commented",
+      "last_activity": "2021-05-20",
+      "from_cache": false
+    },
+    {
+      "number": 4,
+      "title": "Quiet",
+      "url": "https://forge.example/owner/repo/issues/4",
+      "created_at": "2021-05-02T10:00:00Z",
+      "comments": 4,
+      "labels": [],
+      "activity": "no-comments",
+      "last_activity": null,
+      "from_cache": true
+    }
+  ],
+  "missing": [
+    {
+      "number": 5,
+      "title": "Not | cached",
+      "url": "https://forge.example/owner/repo/issues/5"
+    }
+  ],
+  "rate_limited": false
+}
+"#;
+        assert_eq!(triaged(Format::Json, &triage_report()), expected);
+    }
+
+    #[test]
+    fn triaged_as_ndjson() {
+        let expected = concat!(
+            r#"{"number":1,"title":"Crash on \"a, b\"\nwith | in *it*","url":"https://forge.example/owner/repo/issues/1","#,
+            r#""created_at":"2021-05-01T10:00:00Z","comments":1,"labels":["bug","needs `info`"],"#,
+            r#""activity":"last-commented","last_activity":"2021-05-20","from_cache":false}"#,
+            "\n",
+            r#"{"number":4,"title":"Quiet","url":"https://forge.example/owner/repo/issues/4","#,
+            r#""created_at":"2021-05-02T10:00:00Z","comments":4,"labels":[],"#,
+            r#""activity":"no-comments","last_activity":null,"from_cache":true}"#,
+            "\n",
+        );
+        assert_eq!(triaged(Format::Ndjson, &triage_report()), expected);
+    }
+
+    #[test]
+    fn triaged_as_csv() {
+        let expected = "\
+number,title,url,created_at,comments,labels,activity,last_activity,from_cache
+1,\"Crash on \"\"a, b\"\"
+with | in *it*\",https://forge.example/owner/repo/issues/1,2021-05-01,1,bug;needs `info`,last-commented,2021-05-20,false
+4,Quiet,https://forge.example/owner/repo/issues/4,2021-05-02,4,,no-comments,,true
+";
+        assert_eq!(triaged(Format::Csv, &triage_report()), expected);
+    }
+
+    #[test]
+    fn triaged_as_markdown() {
+        let expected = "\
+2 untriaged issues in owner/repo without activity since 2021-06-01:
+
+| Issue | Title | Created | Comments | Last activity | Labels |
+|---|---|---|---:|---|---|
+| [#1](https://forge.example/owner/repo/issues/1) | Crash on \"a, b\" with \\| in \\*it\\* | 2021-05-01 | 1 | commented 2021-05-20 | `bug`, `needs 'info'` |
+| [#4](https://forge.example/owner/repo/issues/4) | Quiet | 2021-05-02 | 4 | no comments (cached) |  |
+
+The activity of these issues isn't cached, so they may be untriaged too:
+
+* [#5](https://forge.example/owner/repo/issues/5) Not \\| cached
+";
+        assert_eq!(triaged(Format::Markdown, &triage_report()), expected);
+    }
+
+    #[test]
+    fn triaged_without_cached_open_issues() {
+        let report = TriageReport {
+            untriaged: Vec::new(),
+            missing: Vec::new(),
+            open_issues_missing: true,
+            ..triage_report()
+        };
+        assert!(triaged(Format::Json, &report).contains("\"status\": \"missing\""));
+        assert_eq!(
+            triaged(Format::Markdown, &report),
+            "No open issues of owner/repo are cached, so none were checked.\n"
+        );
+        let mut out = Vec::new();
+        assert!(write_triaged(&mut out, Format::Csv, &source(), &report).is_err());
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+use serde::Deserialize;
+
+/// A repository given as `owner/name`
+///
+/// Parsing with `str::parse` checks that both parts are present. The owner may
+/// contain slashes itself, as GitLab projects in subgroups do
+/// (`group/subgroup/name`). The default is `rust-lang/rust`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Repo {
+    /// The user, organization or group owning the repository
+    pub owner: String,
+    /// The repository's name
+    pub name: String,
+}
+
+impl Default for Repo {
+    fn default() -> Self {
+        Self {
+            owner: "rust-lang".into(),
+            name: "rust".into(),
+        }
+    }
+}
+
+impl std::str::FromStr for Repo {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.rsplit_once('/') {
+            Some((owner, name))
+                if !name.is_empty()
+                    && !owner.is_empty()
+                    && owner.split('/').all(|p| !p.is_empty()) =>
+            {
+                Ok(Self {
+                    owner: owner.into(),
+                    name: name.into(),
+                })
+            }
+            _ => Err(format!(
+                "invalid repository '{}': expected the form `owner/name`",
+                s
+            )),
+        }
+    }
+}
+
+impl std::convert::TryFrom<String> for Repo {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for Repo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}/{}", self.owner, self.name))
+    }
+}
@@ -0,0 +1,286 @@
+//! The Markdown triage report combining closings and stale issues
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use triage_tracker::{
+    Activity, Actor, Issue, IssueSource, Issues, Result, TriageReport, UntriagedIssue,
+};
+
+use crate::output::markdown_escape;
+
+/// What goes into a report besides the data
+pub(crate) struct ReportOptions<'a> {
+    /// The labels the stale issues were selected by
+    pub(crate) labels: &'a [String],
+    /// Group stale issues by their labels starting with this prefix rather than all labels
+    pub(crate) group_by: Option<&'a str>,
+    /// How many people to list as top openers and closers
+    pub(crate) top: usize,
+}
+
+/// Print a Markdown report of the closings on `days` and the stale issues in `triage`
+pub(crate) fn print_report(
+    source: &dyn IssueSource,
+    days: &[(chrono::NaiveDate, Option<Issues>)],
+    triage: &TriageReport,
+    options: &ReportOptions<'_>,
+) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    // Days are looked up from the most recent, but read best in order
+    let mut days = days.iter().collect::<Vec<_>>();
+    days.sort_by_key(|(date, _)| *date);
+    writeln!(out, "# Triage report for {}", source.repo())?;
+    writeln!(out)?;
+    if let (Some((first, _)), Some((last, _))) = (days.first(), days.last()) {
+        writeln!(out, "Issues opened and closed from {} to {}.", first, last)?;
+        writeln!(out)?;
+    }
+    write_closings(&mut out, &days)?;
+    write_top(
+        &mut out,
+        "Top openers",
+        "Opened",
+        days.iter()
+            .filter_map(|(_, issues)| issues.as_ref())
+            .flat_map(|issues| issues.opened_by()),
+        options.top,
+    )?;
+    write_top(
+        &mut out,
+        "Top closers",
+        "Closed",
+        days.iter()
+            .filter_map(|(_, issues)| issues.as_ref())
+            .flat_map(|issues| issues.closed_by()),
+        options.top,
+    )?;
+    write_stale(&mut out, source, triage, options)?;
+    Ok(())
+}
+
+/// The table of opened, closed and net issues per day with totals
+fn write_closings(
+    out: &mut impl Write,
+    days: &[&(chrono::NaiveDate, Option<Issues>)],
+) -> Result<()> {
+    writeln!(out, "## Net closings")?;
+    writeln!(out)?;
+    writeln!(out, "| Date | Opened | Closed | Net |")?;
+    writeln!(out, "|---|---:|---:|---:|")?;
+    let (mut opened, mut closed, mut net) = (0, 0, 0);
+    let mut incomplete = false;
+    for (date, issues) in days {
+        match issues {
+            Some(issues) => {
+                let day_opened = issues.opened().count();
+                let day_closed = issues.closed().count();
+                opened += day_opened;
+                closed += day_closed;
+                net += issues.diff();
+                incomplete |= !issues.is_complete();
+                writeln!(
+                    out,
+                    "| {}{} | {} | {} | {} |",
+                    date,
+                    if issues.is_complete() {
+                        ""
+                    } else {
+                        " (partial)"
+                    },
+                    day_opened,
+                    day_closed,
+                    signed(issues.diff())
+                )?;
+            }
+            None => {
+                incomplete = true;
+                writeln!(out, "| {} (not cached) | | | |", date)?;
+            }
+        }
+    }
+    writeln!(
+        out,
+        "| **Total** | **{}** | **{}** | **{}** |",
+        opened,
+        closed,
+        signed(net)
+    )?;
+    writeln!(out)?;
+    if incomplete {
+        writeln!(
+            out,
+            "The totals are incomplete: some dates weren't cached or were fetched before they settled (within 24 hours of their end)."
+        )?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// A table of the people who opened or closed the most issues
+fn write_top<'a>(
+    out: &mut impl Write,
+    heading: &str,
+    column: &str,
+    changes: impl Iterator<Item = (&'a Issue, Option<&'a Actor>)>,
+    top: usize,
+) -> Result<()> {
+    let mut counts = HashMap::new();
+    let mut unknown = 0;
+    for (_, actor) in changes {
+        match actor {
+            Some(actor) => *counts.entry(actor.login.as_str()).or_insert(0) += 1,
+            None => unknown += 1,
+        }
+    }
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    writeln!(out, "## {}", heading)?;
+    writeln!(out)?;
+    if counts.is_empty() {
+        writeln!(out, "Nobody.")?;
+    } else {
+        writeln!(out, "| Who | {} |", column)?;
+        writeln!(out, "|---|---:|")?;
+        for (login, count) in counts.into_iter().take(top) {
+            // Logins are written without `@` so that posting the report doesn't notify anybody
+            writeln!(out, "| {} | {} |", markdown_escape(login), count)?;
+        }
+    }
+    writeln!(out)?;
+    if unknown > 0 {
+        writeln!(
+            out,
+            "{} issue{} by unknown users not counted.",
+            unknown,
+            if unknown != 1 { "s" } else { "" }
+        )?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// The stale issues grouped by label
+fn write_stale(
+    out: &mut impl Write,
+    source: &dyn IssueSource,
+    triage: &TriageReport,
+    options: &ReportOptions<'_>,
+) -> Result<()> {
+    writeln!(out, "## Stale issues")?;
+    writeln!(out)?;
+    if triage.open_issues_missing {
+        writeln!(out, "No open issues are cached, so none were checked.")?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    let count = triage.untriaged.len();
+    write!(
+        out,
+        "{} open issue{}",
+        count,
+        if count != 1 { "s" } else { "" }
+    )?;
+    if !options.labels.is_empty() {
+        write!(out, " labelled {}", code_list(options.labels))?;
+    }
+    writeln!(out, " without activity since {}.", triage.since)?;
+    writeln!(out)?;
+    if triage.rate_limited.is_some() {
+        writeln!(out, "Hit a rate limit before all issues were checked.")?;
+        writeln!(out)?;
+    }
+    let mut groups = BTreeMap::<&str, Vec<&UntriagedIssue>>::new();
+    let mut ungrouped = Vec::new();
+    for untriaged in &triage.untriaged {
+        let mut grouped = false;
+        for label in &untriaged.issue.labels {
+            // Every issue has the labels it was selected by, so they make no groups
+            if options.labels.contains(label)
+                || options.group_by.is_some_and(|p| !label.starts_with(p))
+            {
+                continue;
+            }
+            groups.entry(label).or_default().push(untriaged);
+            grouped = true;
+        }
+        if !grouped {
+            ungrouped.push(untriaged);
+        }
+    }
+    for (label, issues) in groups {
+        write_group(out, source, &code_list(&[label]), issues)?;
+    }
+    if !ungrouped.is_empty() {
+        write_group(out, source, "Other", ungrouped)?;
+    }
+    if !triage.missing.is_empty() {
+        writeln!(out, "### Not cached")?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "The activity of these issues isn't cached, so they may be stale too:"
+        )?;
+        writeln!(out)?;
+        for issue in &triage.missing {
+            write_issue(out, source, issue)?;
+            writeln!(out)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_group(
+    out: &mut impl Write,
+    source: &dyn IssueSource,
+    heading: &str,
+    mut issues: Vec<&UntriagedIssue>,
+) -> Result<()> {
+    issues.sort_by_key(|untriaged| untriaged.issue.number);
+    writeln!(out, "### {} ({})", heading, issues.len())?;
+    writeln!(out)?;
+    for untriaged in issues {
+        write_issue(out, source, &untriaged.issue)?;
+        match untriaged.activity {
+            None => writeln!(out, " — no comments")?,
+            Some(Activity::LastCommented(date)) => writeln!(out, " — last commented {}", date)?,
+            Some(Activity::NoActivitySince(date)) => {
+                writeln!(out, " — no activity since {}", date)?
+            }
+        }
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// A list item linking to `issue`, without the line break
+fn write_issue(out: &mut impl Write, source: &dyn IssueSource, issue: &Issue) -> Result<()> {
+    write!(
+        out,
+        "* [#{}]({}) {}",
+        issue.number,
+        source.issue_url(issue.number),
+        markdown_escape(&issue.title)
+    )?;
+    Ok(())
+}
+
+/// Labels as inline code, separated by commas
+fn code_list<S: AsRef<str>>(labels: &[S]) -> String {
+    labels
+        .iter()
+        .map(|l| format!("`{}`", l.as_ref().replace('`', "'")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A change in the number of open issues, with its sign
+fn signed(n: isize) -> String {
+    if n > 0 {
+        format!("+{}", n)
+    } else {
+        n.to_string()
+    }
+}
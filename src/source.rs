@@ -0,0 +1,276 @@
+//! The interface between the analysis logic and the forge hosting the issues
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use log::debug;
+
+use crate::github::GitHub;
+use crate::model::Dated;
+use crate::{Comment, Event, Issue, Repo, Result};
+
+/// A place issues are tracked, like a GitHub repository
+///
+/// `Issues::for_date` and `TriageQuery` only talk to forges through this trait,
+/// so supporting another forge means implementing it and converting the forge's
+/// data into `Issue`, `Event` and `Comment`.
+#[async_trait]
+pub trait IssueSource: Send + Sync {
+    /// The repository or project issues are read from
+    ///
+    /// Cached data is stored per repository.
+    fn repo(&self) -> &Repo;
+
+    /// The link to an issue's web page
+    fn issue_url(&self, number: u32) -> String;
+
+    /// Issues created on `date`
+    ///
+    /// Pull requests may be included as long as they are marked as such.
+    async fn issues_created_on(&self, date: chrono::NaiveDate) -> Result<Vec<Issue>>;
+
+    /// Events on `date` that closed or reopened an issue
+    ///
+    /// Other events may be included as `EventId::Unknown`, and are ignored.
+    async fn state_changes_on(&self, date: chrono::NaiveDate) -> Result<Vec<Event>>;
+
+    /// Whether `state_changes_on` sees every closing and reopening
+    ///
+    /// Sources that only know the most recent closing of an issue (e.g. from a
+    /// search index) return `false`, which keeps their cached results apart.
+    fn sees_all_state_changes(&self) -> bool {
+        true
+    }
+
+    /// A page of open issues having all of `labels`, fewest comments first
+    ///
+    /// Pages are numbered from 1.
+    async fn open_issues(&self, labels: &[String], page: u32) -> Result<Page<Issue>>;
+
+    /// Comments on an issue made since the start of `since`, oldest first
+    async fn comments_since(
+        &self,
+        issue_number: u32,
+        since: chrono::NaiveDate,
+    ) -> Result<Vec<Comment>>;
+
+    /// The GitHub client behind this source, for GitHub-only features like GraphQL
+    fn as_github(&self) -> Option<&GitHub> {
+        None
+    }
+}
+
+/// A page of results along with where to find the rest
+#[derive(Debug)]
+pub struct Page<T> {
+    /// The results on this page
+    pub items: Vec<T>,
+    /// The number of the next page, if there is one
+    pub next: Option<u32>,
+    /// The number of the last page, if there is one after this page
+    pub last: Option<u32>,
+}
+
+/// Fetch all items for `date` from a paginated list sorted from newest to oldest
+///
+/// The first page tells us how many pages there are, which lets us binary search
+/// for the first page reaching back to `date`. From there pages are walked forward
+/// until the date is left behind. Each date therefore needs O(log n) requests plus
+/// the pages spanning it, and every fetched page is reused rather than thrown away.
+pub(crate) async fn fetch_for_date<T, F, Fut>(date: chrono::NaiveDate, fetch: F) -> Result<Vec<T>>
+where
+    T: Dated,
+    F: Fn(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<Page<T>>>,
+{
+    let first = fetch(1).await?;
+    let last_page = first.last.unwrap_or(1);
+    debug!("{} pages to search for '{:?}'", last_page, date);
+    let mut pages = HashMap::new();
+    pages.insert(1, first.items);
+
+    // Find the first page whose oldest item is not more recent than the target date
+    let (mut low, mut high) = (1, last_page);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if let std::collections::hash_map::Entry::Vacant(entry) = pages.entry(middle) {
+            entry.insert(fetch(middle).await?.items);
+        }
+        let reaches_date = pages[&middle]
+            .last()
+            .map(|i| i.date() <= date)
+            .unwrap_or(true);
+        debug!(
+            "Page {} {} back to '{:?}'",
+            middle,
+            if reaches_date {
+                "reaches"
+            } else {
+                "does not reach"
+            },
+            date
+        );
+        if reaches_date {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    let mut items = Vec::new();
+    let mut page_number = low;
+    loop {
+        let page = match pages.remove(&page_number) {
+            Some(page) => page,
+            None => fetch(page_number).await?.items,
+        };
+        let least_recent = page.last().map(|i| i.date());
+        items.extend(page.into_iter().filter(|i| i.is_relevant_for_date(&date)));
+        if least_recent != Some(date) || page_number >= last_page {
+            debug!("All items for '{:?}' found by page {}", date, page_number);
+            break;
+        }
+        debug!("Date '{:?}' spans beyond page {}", date, page_number);
+        page_number += 1;
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// An item of a fake list, dated and numbered
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Item(chrono::NaiveDate, u32);
+
+    impl Dated for Item {
+        fn date(&self) -> chrono::NaiveDate {
+            self.0
+        }
+
+        fn is_relevant_for_date(&self, date: &chrono::NaiveDate) -> bool {
+            &self.0 == date
+        }
+    }
+
+    fn day(day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd(2021, 6, day)
+    }
+
+    /// `per_day` items for each of `days`, newest first like GitHub lists them
+    fn items(days: std::ops::RangeInclusive<u32>, per_day: u32) -> Vec<Item> {
+        days.rev()
+            .flat_map(|d| (0..per_day).map(move |i| Item(day(d), d * 100 + i)))
+            .collect()
+    }
+
+    /// Look up `date` in `items` split into pages of `per_page`, returning the
+    /// items found and the pages fetched
+    async fn search(
+        items: &[Item],
+        per_page: usize,
+        date: chrono::NaiveDate,
+    ) -> (Vec<Item>, Vec<u32>) {
+        let pages = items.chunks(per_page).collect::<Vec<_>>();
+        let last = pages.len().max(1) as u32;
+        let fetched = RefCell::new(Vec::new());
+        let found = fetch_for_date(date, |page| {
+            fetched.borrow_mut().push(page);
+            // Like GitHub's `Link` header, there is no `last` on the last page
+            std::future::ready(Ok(Page {
+                items: pages
+                    .get(page as usize - 1)
+                    .map_or(Vec::new(), |p| p.to_vec()),
+                next: Some(page + 1).filter(|&next| next <= last),
+                last: Some(last).filter(|&last| last > page),
+            }))
+        })
+        .await
+        .unwrap();
+        (found, fetched.into_inner())
+    }
+
+    #[tokio::test]
+    async fn finds_every_date_with_any_page_size() {
+        let items = items(1..=20, 3);
+        for per_page in [1, 2, 3, 4, 7, 10, 60, 100] {
+            for d in 1..=20 {
+                let expected = items
+                    .iter()
+                    .filter(|i| i.0 == day(d))
+                    .copied()
+                    .collect::<Vec<_>>();
+                let (found, _) = search(&items, per_page, day(d)).await;
+                assert_eq!(found, expected, "{} per page, June {}", per_page, d);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn one_page_only() {
+        let items = items(1..=5, 2);
+        let (found, fetched) = search(&items, 100, day(3)).await;
+        assert_eq!(found, [Item(day(3), 300), Item(day(3), 301)]);
+        assert_eq!(fetched, [1]);
+    }
+
+    #[tokio::test]
+    async fn date_on_the_first_page() {
+        let items = items(1..=30, 2);
+        let (found, fetched) = search(&items, 10, day(30)).await;
+        assert_eq!(found, [Item(day(30), 3000), Item(day(30), 3001)]);
+        // The binary search works its way back to the first page, which is reused
+        assert_eq!(fetched.iter().filter(|&&p| p == 1).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn date_on_the_last_page() {
+        let items = items(1..=30, 2);
+        let (found, fetched) = search(&items, 10, day(1)).await;
+        assert_eq!(found, [Item(day(1), 100), Item(day(1), 101)]);
+        assert!(fetched.contains(&6));
+        assert!(!fetched.contains(&7));
+    }
+
+    #[tokio::test]
+    async fn date_spanning_pages() {
+        let items = items(1..=10, 10);
+        let (found, fetched) = search(&items, 4, day(5)).await;
+        assert_eq!(found.len(), 10);
+        assert!(found.iter().all(|i| i.0 == day(5)));
+        // Each page is fetched once, even if the binary search saw it already
+        let mut unique = fetched.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), fetched.len());
+    }
+
+    #[tokio::test]
+    async fn needs_few_requests_for_long_lists() {
+        let items = items(1..=30, 100);
+        let (found, fetched) = search(&items, 10, day(15)).await;
+        assert_eq!(found.len(), 100);
+        // 300 pages: about log2(300) to find the date, then the 10 or 11 pages it spans
+        assert!(fetched.len() <= 9 + 1 + 11, "fetched {:?}", fetched);
+    }
+
+    #[tokio::test]
+    async fn dates_past_the_ends() {
+        let items = items(10..=20, 3);
+        let (found, _) = search(&items, 4, day(5)).await;
+        assert!(found.is_empty());
+        let (found, _) = search(&items, 4, day(25)).await;
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_list() {
+        let (found, fetched) = search(&[], 10, day(5)).await;
+        assert!(found.is_empty());
+        assert_eq!(fetched, [1]);
+    }
+}
@@ -0,0 +1,436 @@
+//! Finding issues that have not been active for a while
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::error::RateLimit;
+use crate::github::GitHub;
+use crate::source::IssueSource;
+use crate::{Comment, Error, Issue, Result};
+
+/// A query for open issues that have not been commented on since a given date
+///
+/// ```no_run
+/// # async fn example(github: &triage_tracker::github::GitHub) -> triage_tracker::Result<()> {
+/// let cache = triage_tracker::Cache::in_memory()?;
+/// let report = triage_tracker::TriageQuery::new()
+///     .labels(&["A-diagnostics"])
+///     .run(github, &cache)
+///     .await?;
+/// for untriaged in report.untriaged {
+///     println!("{}", github.issue_url(untriaged.issue.number));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TriageQuery {
+    labels: Vec<String>,
+    since: Option<chrono::NaiveDate>,
+    graphql: bool,
+    offline: bool,
+}
+
+/// The outcome of running a `TriageQuery`
+#[derive(Debug)]
+pub struct TriageReport {
+    /// The date the query looked for activity since
+    pub since: chrono::NaiveDate,
+    /// Issues without activity since the query's date
+    pub untriaged: Vec<UntriagedIssue>,
+    /// Set if rate limiting stopped the query before all issues were checked
+    pub rate_limited: Option<RateLimit>,
+    /// Open issues whose activity isn't cached, when running offline
+    pub missing: Vec<Issue>,
+    /// Set when running offline without any open issues cached, so that none were checked
+    pub open_issues_missing: bool,
+}
+
+/// An open issue without activity since a query's date
+#[derive(Debug)]
+pub struct UntriagedIssue {
+    /// The open issue
+    pub issue: Issue,
+    /// What is known about the issue's last activity, `None` if it has no comments
+    pub activity: Option<Activity>,
+    /// Whether the activity was taken from the cache rather than fetched by the query
+    pub from_cache: bool,
+}
+
+impl UntriagedIssue {
+    fn new(issue: Issue, activity: Option<Activity>, from_cache: bool) -> Self {
+        Self {
+            issue,
+            activity,
+            from_cache,
+        }
+    }
+}
+
+impl TriageQuery {
+    /// A query for all open issues, with every option at its default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only consider issues having all of `labels`
+    pub fn labels<L: AsRef<str>>(mut self, labels: &[L]) -> Self {
+        self.labels = labels.iter().map(|l| l.as_ref().to_owned()).collect();
+        self
+    }
+
+    /// Look for issues without activity since `date` [default: one year ago]
+    pub fn since(mut self, date: chrono::NaiveDate) -> Self {
+        self.since = Some(date);
+        self
+    }
+
+    /// Use GitHub's GraphQL API, which needs a token but far fewer requests
+    pub fn graphql(mut self, graphql: bool) -> Self {
+        self.graphql = graphql;
+        self
+    }
+
+    /// Answer from the cache alone, without sending any requests
+    ///
+    /// Open issues and their activity are taken as they were last seen, however
+    /// long ago that was. Issues whose activity isn't known are reported as
+    /// missing, as is the whole list if no open issues are cached.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Run the query, caching what is learned about each issue
+    ///
+    /// Hitting a rate limit is not an error: the issues found so far are
+    /// returned along with the limit.
+    pub async fn run(&self, source: &dyn IssueSource, cache: &Cache) -> Result<TriageReport> {
+        let mut untriaged = Vec::new();
+        let mut missing = Vec::new();
+        let mut open_issues_missing = false;
+        let mut cache = TriageCache { cache };
+        let since = self.since.unwrap_or_else(|| {
+            let today = chrono::Local::today().naive_local();
+            today - chrono::Duration::days(365)
+        });
+        let result = if self.offline {
+            perform_offline_triage_loop(
+                &self.labels,
+                since,
+                &mut untriaged,
+                &mut missing,
+                &mut open_issues_missing,
+                &cache,
+            )
+        } else if self.graphql {
+            let github = source
+                .as_github()
+                .ok_or("the GraphQL API is only available for GitHub")?;
+            perform_graphql_triage_loop(github, &self.labels, since, &mut untriaged, &mut cache)
+                .await
+        } else {
+            perform_triage_loop(source, &self.labels, since, &mut untriaged, &mut cache).await
+        };
+        let rate_limited = match result {
+            Ok(()) => None,
+            Err(Error::RateLimited(limit)) => Some(limit),
+            Err(e) => return Err(e),
+        };
+        Ok(TriageReport {
+            since,
+            untriaged,
+            rate_limited,
+            missing,
+            open_issues_missing,
+        })
+    }
+}
+
+/// The triage state stored in a repository's cache
+///
+/// Every insertion is written to the cache right away, so nothing learned is
+/// lost when a run stops early.
+pub(crate) struct TriageCache<'a> {
+    cache: &'a Cache,
+}
+
+/// How long the activity found for an issue is used before checking the issue again
+pub(crate) const ACTIVITY_TTL_HOURS: i64 = 24;
+
+/// Type of activity we know about an issue
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub enum Activity {
+    /// We have not seen an activity since the given date, but
+    /// there might be some before
+    NoActivitySince(chrono::NaiveDate),
+    /// The last comment was on the given date
+    LastCommented(chrono::NaiveDate),
+}
+
+/// The result of looking up in the cache
+enum CacheResult {
+    Fresh(Activity),
+    Stale(Activity),
+    NotFound,
+}
+
+impl TriageCache<'_> {
+    /// Get the cached activity for an issue
+    fn get(&self, issue_number: &u32, ttl: Option<chrono::Duration>) -> Result<CacheResult> {
+        let result = self
+            .cache
+            .activity(*issue_number)?
+            .map(|(activity, last_checked)| {
+                debug!("Issue #{} found in triage cache", issue_number);
+                let now = chrono::Utc::now();
+                let ago = ttl.map(|ttl| now - ttl);
+                if ago.map(|ago| last_checked < ago).unwrap_or(false) {
+                    CacheResult::Stale(activity)
+                } else {
+                    CacheResult::Fresh(activity)
+                }
+            })
+            .unwrap_or(CacheResult::NotFound);
+        Ok(result)
+    }
+
+    fn insert(&mut self, issue_number: u32, activity: Activity) {
+        debug!("Inserting issue #{} into cache", issue_number);
+        if let Err(e) = self.cache.set_activity(issue_number, activity) {
+            eprintln!(
+                "Warning: failed to cache activity of #{}: {}",
+                issue_number, e
+            );
+        }
+    }
+
+    /// Remember the current state of issues
+    fn store_issues(&self, issues: &[Issue]) {
+        if let Err(e) = self.cache.store_issues(issues) {
+            eprintln!("Warning: failed to cache issues: {}", e);
+        }
+    }
+
+    /// Remember the comments made on an issue
+    fn store_comments(&self, issue_number: u32, comments: &[Comment]) {
+        if let Err(e) = self.cache.store_comments(issue_number, comments) {
+            eprintln!(
+                "Warning: failed to cache comments on #{}: {}",
+                issue_number, e
+            );
+        }
+    }
+}
+
+/// Like `perform_triage_loop` but using the GraphQL API
+///
+/// Each page of issues already includes their most recent comment, so the cache
+/// is only updated rather than consulted.
+async fn perform_graphql_triage_loop(
+    github: &GitHub,
+    tags: &[String],
+    last_active_yard_stick: chrono::NaiveDate,
+    untriaged: &mut Vec<UntriagedIssue>,
+    cache: &mut TriageCache<'_>,
+) -> Result<()> {
+    let mut cursor = None;
+    loop {
+        let page = github.fetch_triage_page(tags, cursor.as_deref()).await?;
+        let (issues, last_commented): (Vec<_>, Vec<_>) = page
+            .issues
+            .into_iter()
+            .map(|i| (i.issue, i.last_commented))
+            .unzip();
+        cache.store_issues(&issues);
+        for (issue, last_commented) in issues.into_iter().zip(last_commented) {
+            match last_commented {
+                None => {
+                    debug!("Issue #{} has no comments", issue.number);
+                    if issue.created_at.date().naive_utc() < last_active_yard_stick {
+                        untriaged.push(UntriagedIssue::new(issue, None, false));
+                    }
+                }
+                Some(last_commented) => {
+                    let last_commented = last_commented.date().naive_utc();
+                    let activity = Activity::LastCommented(last_commented);
+                    cache.insert(issue.number, activity);
+                    if last_commented < last_active_yard_stick {
+                        debug!(
+                            "Issue #{} was last commented on ({:?}) before the yard stick ({:?})",
+                            issue.number, last_commented, last_active_yard_stick
+                        );
+                        untriaged.push(UntriagedIssue::new(issue, Some(activity), false));
+                    }
+                }
+            }
+        }
+        cursor = page.next;
+        if cursor.is_none() {
+            debug!("Reached the last page of issues");
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Like `perform_triage_loop` but only looking at the cache
+///
+/// Open issues whose activity can't be determined from the cache are added to
+/// `missing`, and `open_issues_missing` is set if no open issues are cached at all.
+fn perform_offline_triage_loop(
+    tags: &[String],
+    last_active_yard_stick: chrono::NaiveDate,
+    untriaged: &mut Vec<UntriagedIssue>,
+    missing: &mut Vec<Issue>,
+    open_issues_missing: &mut bool,
+    cache: &TriageCache<'_>,
+) -> Result<()> {
+    let open_issues = cache.cache.open_issues()?;
+    if open_issues.is_empty() {
+        debug!("No open issues are cached");
+        *open_issues_missing = true;
+    }
+    for issue in open_issues {
+        if issue.is_pull_request() || !issue.has_labels(tags) {
+            continue;
+        }
+        if issue.comments == 0 {
+            if issue.created_at.date().naive_local() < last_active_yard_stick {
+                untriaged.push(UntriagedIssue::new(issue, None, true));
+            }
+            continue;
+        }
+        // However stale, the cached activity is the best there is
+        match cache.get(&issue.number, None)? {
+            CacheResult::Fresh(activity @ Activity::LastCommented(last_comment)) => {
+                if last_comment < last_active_yard_stick {
+                    untriaged.push(UntriagedIssue::new(issue, Some(activity), true));
+                }
+            }
+            CacheResult::Fresh(activity @ Activity::NoActivitySince(no_activity_since))
+                if no_activity_since <= last_active_yard_stick =>
+            {
+                untriaged.push(UntriagedIssue::new(issue, Some(activity), true));
+            }
+            _ => {
+                debug!("Activity of issue #{} isn't cached", issue.number);
+                missing.push(issue);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check which issues given with `tags` were last active before the `last_active_yard_stick`
+async fn perform_triage_loop(
+    source: &dyn IssueSource,
+    tags: &[String],
+    last_active_yard_stick: chrono::NaiveDate,
+    untriaged: &mut Vec<UntriagedIssue>,
+    cache: &mut TriageCache<'_>,
+) -> Result<()> {
+    let mut next_page = Some(1);
+    while let Some(page) = next_page {
+        let issues = source.open_issues(tags, page).await?;
+        cache.store_issues(&issues.items);
+        next_page = issues.next;
+        if next_page.is_none() {
+            debug!("Reached the last page of issues");
+        }
+        for issue in issues.items {
+            if issue.is_pull_request() {
+                continue;
+            }
+            if issue.comments == 0 {
+                // Issue has no comments
+                debug!("Issue #{} has no comments", issue.number);
+                let created_at = issue.created_at.date().naive_local();
+                let issue_number = issue.number;
+                if created_at < last_active_yard_stick {
+                    debug!(
+                        "Issue #{} without comments was created before selected date",
+                        issue_number
+                    );
+                    untriaged.push(UntriagedIssue::new(issue, None, false));
+                }
+                continue;
+            }
+
+            match cache.get(
+                &issue.number,
+                Some(chrono::Duration::hours(ACTIVITY_TTL_HOURS)),
+            )? {
+                CacheResult::Fresh(activity @ Activity::LastCommented(last_comment)) => {
+                    let issue_number = issue.number;
+                    let direction = if last_comment < last_active_yard_stick {
+                        untriaged.push(UntriagedIssue::new(issue, Some(activity), true));
+                        "before"
+                    } else {
+                        "after"
+                    };
+                    debug!(
+                        "Issue #{} was last commented on ({:?}) {} the yard stick ({:?})",
+                        issue_number, last_comment, direction, last_active_yard_stick
+                    );
+                    // We have an answer so go on to next issue
+                    continue;
+                }
+                CacheResult::Fresh(activity @ Activity::NoActivitySince(no_activity_since)) => {
+                    if no_activity_since <= last_active_yard_stick {
+                        debug!(
+                            "Issue #{} was last active (sometime before {:?}) before the yard stick ({:?})",
+                            issue.number, no_activity_since, last_active_yard_stick
+                        );
+
+                        untriaged.push(UntriagedIssue::new(issue, Some(activity), true));
+
+                        // We have an answer so go on to next issue
+                        continue;
+                    } else {
+                        debug!(
+                            "The yard stick ({:?}) is before when we have visibility ({:?}) on issue #{}",
+                            last_active_yard_stick, no_activity_since, issue.number
+                        );
+                        // We don't know when the issue was last active, we need to determine that
+                    }
+                }
+                CacheResult::Stale(Activity::LastCommented(last_commented))
+                    if last_commented > last_active_yard_stick =>
+                {
+                    // Even though the result is stale, we still know that there is a comment more recent than
+                    // the yard stick. It's possible there's an even *more* recent comment, but that's not relevant.
+                    continue;
+                }
+                _ => {
+                    debug!("Issue #{} not found in cache.", issue.number);
+                }
+            }
+
+            debug!(
+                "State of issue #{} could not be determined from cache. Fetching comments...",
+                issue.number
+            );
+
+            let comments = source
+                .comments_since(issue.number, last_active_yard_stick)
+                .await?;
+            cache.store_comments(issue.number, &comments);
+            match comments.last() {
+                None => {
+                    let activity = Activity::NoActivitySince(last_active_yard_stick);
+                    cache.insert(issue.number, activity);
+                    untriaged.push(UntriagedIssue::new(issue, Some(activity), false));
+                }
+                Some(comment) => {
+                    cache.insert(
+                        issue.number,
+                        Activity::LastCommented(comment.created_at.naive_local().date()),
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
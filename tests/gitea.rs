@@ -0,0 +1,200 @@
+//! The Gitea backend against a local mock of the Gitea API
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use triage_tracker::gitea::Gitea;
+use triage_tracker::{Activity, Cache, ClientOptions, Issues, TriageQuery};
+
+/// A response of the mock server: status, extra headers and JSON body
+type Response = (u16, Vec<(&'static str, String)>, String);
+
+/// A Gitea API served on a local port, recording the requests it got
+struct MockServer {
+    api_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Answer each request with what `respond` returns for its path and query
+    fn start(respond: impl Fn(&str) -> Response + Send + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}/api/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers; requests to the API have no body
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let target = request_line.split(' ').nth(1).unwrap_or_default();
+                seen.lock().unwrap().push(target.to_owned());
+                let (status, headers, body) = respond(target);
+                let mut response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Self { api_url, requests }
+    }
+
+    fn gitea(&self) -> Gitea {
+        let mut options = ClientOptions {
+            api_url: self.api_url.clone(),
+            ..Default::default()
+        };
+        options.retry.max_retries = 0;
+        Gitea::new("owner/repo".parse().unwrap(), None, options).unwrap()
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn ok(body: &str) -> Response {
+    (200, Vec::new(), body.to_owned())
+}
+
+/// The first of two pages, linking to the second like Gitea does
+fn first_page(path: &str, body: &str) -> Response {
+    let link = format!(
+        r#"<https://gitea.example{}?limit=50&page=2>; rel="next",<https://gitea.example{}?limit=50&page=2>; rel="last""#,
+        path, path
+    );
+    (200, vec![("Link", link)], body.to_owned())
+}
+
+const REPO: &str = "/api/v1/repos/owner/repo";
+
+const OPENED: &str = r#"{"number": 1, "title": "Opened", "comments": 0, "pull_request": null,
+    "labels": [{"id": 1, "name": "bug"}], "user": {"login": "ann"},
+    "created_at": "2021-06-05T10:00:00Z", "closed_at": null}"#;
+const MERGED: &str = r#"{"number": 4, "title": "Merged", "comments": 0,
+    "pull_request": {"merged": true, "merged_at": "2021-06-05T11:00:00Z"},
+    "labels": [], "user": {"login": "ben"},
+    "created_at": "2021-06-05T09:00:00Z", "closed_at": "2021-06-05T11:00:00Z"}"#;
+const CLOSED: &str = r#"{"number": 2, "title": "Closed", "comments": 1, "labels": [],
+    "user": {"login": "ben"}, "created_at": "2021-06-01T10:00:00Z",
+    "closed_at": "2021-06-05T12:00:00Z"}"#;
+const STALE: &str = r#"{"number": 3, "title": "Stale", "comments": 2,
+    "labels": [{"id": 1, "name": "bug"}], "user": {"login": "ann"},
+    "created_at": "2021-05-01T10:00:00Z", "closed_at": null}"#;
+
+fn respond(target: &str) -> Response {
+    let path = target.split('?').next().unwrap_or_default();
+    let page_2 = target.contains("page=2");
+    match path.strip_prefix(REPO) {
+        Some("/issues") if target.contains("state=open") => ok(&format!("[{}, {}]", OPENED, STALE)),
+        Some("/issues") if page_2 => ok(&format!("[{}, {}]", CLOSED, STALE)),
+        // Some servers list pull requests even when asked for issues only
+        Some("/issues") => first_page(path, &format!("[{}, {}]", OPENED, MERGED)),
+        Some("/issues/2/timeline") => ok(r#"[
+            {"type": "comment", "user": {"login": "ann"}, "created_at": "2021-06-05T11:00:00Z"},
+            {"type": "close", "user": {"login": "cy"}, "created_at": "2021-06-05T12:00:00Z"}
+        ]"#),
+        Some("/issues/4/timeline") => ok(r#"[
+            {"type": "close", "user": {"login": "ben"}, "created_at": "2021-06-05T11:00:00Z"}
+        ]"#),
+        Some(path) if path.ends_with("/timeline") => ok("[]"),
+        Some("/issues/1/comments") => ok("[]"),
+        // Gitea's `since` also matches older comments edited since
+        Some("/issues/3/comments") => ok(r#"[
+            {"body": "Still happening", "created_at": "2021-05-02T10:00:00Z"}
+        ]"#),
+        _ => (404, Vec::new(), r#"{"message": "Not Found"}"#.to_owned()),
+    }
+}
+
+#[tokio::test]
+async fn closings_skip_pull_requests_across_pages() {
+    let server = MockServer::start(respond);
+    let gitea = server.gitea();
+    let cache = Cache::in_memory().unwrap();
+    let date = chrono::NaiveDate::from_ymd(2021, 6, 5);
+    let issues = Issues::for_date(&gitea, &cache, date).await.unwrap();
+    let opened = issues.opened().map(|i| i.number).collect::<Vec<_>>();
+    let closed = issues.closed().map(|i| i.number).collect::<Vec<_>>();
+    assert_eq!(opened, [1]);
+    assert_eq!(closed, [2]);
+    assert_eq!(issues.diff(), 0);
+    assert!(issues.is_complete());
+    let closers = issues
+        .closed_by()
+        .map(|(_, actor)| actor.map(|a| a.login.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(closers, [Some("cy".to_owned())]);
+    let requests = server.requests();
+    assert!(requests
+        .iter()
+        .any(|r| r.contains("since=2021-06-05T00%3A00%3A00Z")));
+    // Both pages are fetched, for both the issues and the events
+    let pages = |page: &str| {
+        requests
+            .iter()
+            .filter(|r| r.starts_with(&format!("{}/issues?", REPO)) && r.contains(page))
+            .count()
+    };
+    assert_eq!(pages("page=1"), 2);
+    assert_eq!(pages("page=2"), 2);
+}
+
+#[tokio::test]
+async fn pull_requests_are_marked() {
+    let server = MockServer::start(respond);
+    let gitea = server.gitea();
+    let date = chrono::NaiveDate::from_ymd(2021, 6, 5);
+    let issues = gitea.fetch_issues_updated_since(date).await.unwrap();
+    let pull_requests = issues
+        .iter()
+        .map(|i| (i.number, i.is_pull_request()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        pull_requests,
+        [(1, false), (4, true), (2, false), (3, false)]
+    );
+}
+
+#[tokio::test]
+async fn stale_issues_ignore_edited_old_comments() {
+    let server = MockServer::start(respond);
+    let gitea = server.gitea();
+    let cache = Cache::in_memory().unwrap();
+    let since = chrono::NaiveDate::from_ymd(2021, 6, 1);
+    let report = TriageQuery::new()
+        .labels(&["bug"])
+        .since(since)
+        .run(&gitea, &cache)
+        .await
+        .unwrap();
+    // Issue 1 was opened after the date, and the comment on issue 3 is older
+    let untriaged = report
+        .untriaged
+        .iter()
+        .map(|u| (u.issue.number, u.activity))
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        untriaged[..],
+        [(3, Some(Activity::NoActivitySince(date)))] if date == since
+    ));
+    assert!(report.rate_limited.is_none());
+    assert!(server
+        .requests()
+        .iter()
+        .any(|r| r.contains("state=open") && r.contains("labels=bug")));
+}
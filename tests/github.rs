@@ -0,0 +1,222 @@
+//! Rate limits and retries of the GitHub backend against a local mock of the GitHub API
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use triage_tracker::error::RateLimitKind;
+use triage_tracker::github::GitHub;
+use triage_tracker::{ClientOptions, Comment, Error, Result};
+
+/// A response of the mock server: status, extra headers and JSON body
+type Response = (u16, Vec<(&'static str, String)>, String);
+
+/// A GitHub API served on a local port, recording the requests it got
+struct MockServer {
+    api_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Answer each request with what `respond` returns for its path and query
+    fn start(respond: impl Fn(&str) -> Response + Send + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers; requests to the API have no body
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let target = request_line.split(' ').nth(1).unwrap_or_default();
+                seen.lock().unwrap().push(target.to_owned());
+                let (status, headers, body) = respond(target);
+                let mut response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Self { api_url, requests }
+    }
+
+    /// A client retrying transient failures `max_retries` times without waiting long
+    fn github(&self, max_retries: u32) -> GitHub {
+        let mut options = ClientOptions {
+            api_url: self.api_url.clone(),
+            ..Default::default()
+        };
+        options.retry.max_retries = max_retries;
+        options.retry.initial_backoff = Duration::from_millis(1);
+        options.retry.max_backoff = Duration::from_millis(1);
+        GitHub::new("owner/repo".parse().unwrap(), None, options).unwrap()
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn ok(body: &str) -> Response {
+    (200, Vec::new(), body.to_owned())
+}
+
+fn status(status: u16, body: &str) -> Response {
+    (status, Vec::new(), body.to_owned())
+}
+
+/// Answer with `failure` for the first `failures` requests, then with a comment
+fn failing(failures: u32, failure: Response) -> impl Fn(&str) -> Response + Send + 'static {
+    let attempts = AtomicU32::new(0);
+    move |_| {
+        if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+            failure.clone()
+        } else {
+            ok(r#"[{"body": "Hello", "created_at": "2021-06-05T10:00:00Z"}]"#)
+        }
+    }
+}
+
+/// Fetch the comments on issue 1, a single GET of the API
+async fn comments(github: &GitHub) -> Result<Vec<Comment>> {
+    github.fetch_comment_page(1, 1, 100, None).await
+}
+
+#[tokio::test]
+async fn primary_rate_limits_are_not_retried() {
+    let server = MockServer::start(|_| {
+        (
+            403,
+            vec![
+                ("x-ratelimit-remaining", "0".to_owned()),
+                ("x-ratelimit-reset", "1622887200".to_owned()),
+            ],
+            r#"{"message": "API rate limit exceeded"}"#.to_owned(),
+        )
+    });
+    let error = comments(&server.github(3))
+        .await
+        .expect_err("rate limits are errors");
+    let reset = chrono::NaiveDate::from_ymd(2021, 6, 5).and_hms(10, 0, 0);
+    assert!(matches!(
+        error,
+        Error::RateLimited(limit)
+            if limit.forge == "GitHub"
+                && limit.kind == RateLimitKind::Primary
+                && limit.retry_at.map(|t| t.naive_utc()) == Some(reset)
+    ));
+    assert_eq!(
+        server.requests(),
+        ["/repos/owner/repo/issues/1/comments?per_page=100&page=1"]
+    );
+}
+
+#[tokio::test]
+async fn secondary_rate_limits_are_not_retried() {
+    let retry_after = (
+        403,
+        vec![("Retry-After", "30".to_owned())],
+        r#"{"message": "Slow down"}"#.to_owned(),
+    );
+    let in_body = status(
+        403,
+        r#"{"message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."}"#,
+    );
+    let too_many = status(429, r#"{"message": "Too many requests"}"#);
+    for response in [retry_after, in_body, too_many] {
+        let server = MockServer::start(move |_| response.clone());
+        let before = chrono::Utc::now();
+        let error = comments(&server.github(3))
+            .await
+            .expect_err("rate limits are errors");
+        match error {
+            Error::RateLimited(limit) => {
+                assert_eq!(limit.forge, "GitHub");
+                assert_eq!(limit.kind, RateLimitKind::Secondary);
+                // Without a time from GitHub a minute is waited
+                let retry_at = limit
+                    .retry_at
+                    .expect("secondary rate limits have a retry time");
+                assert!(retry_at >= before + chrono::Duration::seconds(29));
+                assert!(retry_at <= chrono::Utc::now() + chrono::Duration::seconds(61));
+            }
+            e => panic!("expected a rate limit, got '{}'", e),
+        }
+        assert_eq!(server.requests().len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn forbidden_without_rate_limit_is_a_permission_error() {
+    let server = MockServer::start(|_| {
+        status(
+            403,
+            r#"{"message": "Resource not accessible by integration"}"#,
+        )
+    });
+    let error = comments(&server.github(3))
+        .await
+        .expect_err("403 is an error");
+    assert!(matches!(error, Error::Other(_)));
+    let message = error.to_string();
+    assert!(message.contains("GitHub denied access"), "{}", message);
+    assert!(
+        message.contains("Resource not accessible by integration"),
+        "{}",
+        message
+    );
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn server_errors_are_retried() {
+    for code in [500, 502, 503, 504] {
+        let server = MockServer::start(failing(2, status(code, r#"{"message": "Oops"}"#)));
+        let comments = comments(&server.github(3))
+            .await
+            .unwrap_or_else(|e| panic!("{} wasn't retried: {}", code, e));
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].body, "Hello");
+        assert_eq!(server.requests().len(), 3, "{}", code);
+    }
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    for code in [400, 401, 404, 410, 422] {
+        let server = MockServer::start(failing(1, status(code, r#"{"message": "No"}"#)));
+        let result = comments(&server.github(3)).await;
+        assert!(matches!(result, Err(Error::Other(_))), "{}", code);
+        assert_eq!(server.requests().len(), 1, "{}", code);
+    }
+}
+
+#[tokio::test]
+async fn retries_stop_at_max_retries() {
+    for max_retries in [0, 1, 4] {
+        let server = MockServer::start(|_| status(503, r#"{"message": "Unavailable"}"#));
+        let result = comments(&server.github(max_retries)).await;
+        assert!(matches!(result, Err(Error::Other(_))));
+        assert_eq!(server.requests().len() as u32, max_retries + 1);
+    }
+    // Succeeding on the last retry is a success
+    let server = MockServer::start(failing(2, status(502, "Bad gateway")));
+    assert!(comments(&server.github(2)).await.is_ok());
+    assert_eq!(server.requests().len(), 3);
+}
@@ -0,0 +1,195 @@
+//! The GitLab backend against a local mock of the GitLab API
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use triage_tracker::gitlab::GitLab;
+use triage_tracker::{Activity, Cache, ClientOptions, Error, Issues, TriageQuery};
+
+/// A response of the mock server: status, extra headers and JSON body
+type Response = (u16, Vec<(&'static str, String)>, String);
+
+/// A GitLab API served on a local port, recording the requests it got
+struct MockServer {
+    api_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Answer each request with what `respond` returns for its path and query
+    fn start(respond: impl Fn(&str) -> Response + Send + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}/api/v4", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers; requests to the API have no body
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let target = request_line.split(' ').nth(1).unwrap_or_default();
+                seen.lock().unwrap().push(target.to_owned());
+                let (status, headers, body) = respond(target);
+                let mut response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Self { api_url, requests }
+    }
+
+    fn gitlab(&self) -> GitLab {
+        let mut options = ClientOptions {
+            api_url: self.api_url.clone(),
+            ..Default::default()
+        };
+        options.retry.max_retries = 0;
+        GitLab::new("group/project".parse().unwrap(), None, options).unwrap()
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn ok(body: &str) -> Response {
+    (200, Vec::new(), body.to_owned())
+}
+
+const PROJECT: &str = "/api/v4/projects/group%2Fproject";
+
+const OPENED: &str = r#"{"iid": 1, "title": "Opened", "user_notes_count": 0, "labels": ["bug"],
+    "author": {"username": "ann"}, "created_at": "2021-06-05T10:00:00Z", "closed_at": null}"#;
+const CLOSED: &str = r#"{"iid": 2, "title": "Closed", "user_notes_count": 1, "labels": [],
+    "author": {"username": "ben"}, "created_at": "2021-06-01T10:00:00Z",
+    "closed_at": "2021-06-05T12:00:00Z"}"#;
+const STALE: &str = r#"{"iid": 3, "title": "Stale", "user_notes_count": 2, "labels": ["bug"],
+    "author": {"username": "ann"}, "created_at": "2021-05-01T10:00:00Z", "closed_at": null}"#;
+
+fn respond(target: &str) -> Response {
+    let path = target.split('?').next().unwrap_or_default();
+    match path.strip_prefix(PROJECT) {
+        Some("/issues") if target.contains("state=opened") => {
+            ok(&format!("[{}, {}]", OPENED, STALE))
+        }
+        Some("/issues") => ok(&format!("[{}, {}, {}]", OPENED, CLOSED, STALE)),
+        Some("/issues/2/resource_state_events") => ok(r#"[
+            {"user": {"username": "cy"}, "created_at": "2021-06-05T12:00:00Z", "state": "closed"}
+        ]"#),
+        Some(path) if path.ends_with("/resource_state_events") => ok("[]"),
+        Some("/issues/1/notes") => ok(r#"[
+            {"body": "added ~bug", "system": true, "created_at": "2021-06-05T10:00:00Z"}
+        ]"#),
+        Some("/issues/3/notes") => ok(r#"[
+            {"body": "Still happening", "system": false, "created_at": "2021-05-02T10:00:00Z"},
+            {"body": "changed the description", "system": true, "created_at": "2021-06-02T10:00:00Z"}
+        ]"#),
+        _ => (
+            404,
+            Vec::new(),
+            r#"{"message": "404 Not Found"}"#.to_owned(),
+        ),
+    }
+}
+
+#[tokio::test]
+async fn closings_come_from_issues_and_state_events() {
+    let server = MockServer::start(respond);
+    let gitlab = server.gitlab();
+    let cache = Cache::in_memory().unwrap();
+    let date = chrono::NaiveDate::from_ymd(2021, 6, 5);
+    let issues = Issues::for_date(&gitlab, &cache, date).await.unwrap();
+    let opened = issues.opened().map(|i| i.number).collect::<Vec<_>>();
+    let closed = issues.closed().map(|i| i.number).collect::<Vec<_>>();
+    assert_eq!(opened, [1]);
+    assert_eq!(closed, [2]);
+    assert_eq!(issues.diff(), 0);
+    assert!(issues.is_complete());
+    let closers = issues
+        .closed_by()
+        .map(|(_, actor)| actor.map(|a| a.login.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(closers, [Some("cy".to_owned())]);
+    assert!(server
+        .requests()
+        .iter()
+        .any(|r| r.contains("created_after=2021-06-05T00%3A00%3A00Z")));
+}
+
+#[tokio::test]
+async fn stale_issues_skip_system_notes() {
+    let server = MockServer::start(respond);
+    let gitlab = server.gitlab();
+    let cache = Cache::in_memory().unwrap();
+    let since = chrono::NaiveDate::from_ymd(2021, 6, 1);
+    let report = TriageQuery::new()
+        .labels(&["bug"])
+        .since(since)
+        .run(&gitlab, &cache)
+        .await
+        .unwrap();
+    // Issue 1 was opened after the date, and the system note on issue 3 doesn't count
+    let untriaged = report
+        .untriaged
+        .iter()
+        .map(|u| (u.issue.number, u.activity))
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        untriaged[..],
+        [(3, Some(Activity::NoActivitySince(date)))] if date == since
+    ));
+    assert!(report.rate_limited.is_none());
+    assert!(server.requests().iter().any(|r| r.contains("labels=bug")));
+}
+
+#[tokio::test]
+async fn offline_triage_without_cached_issues_is_missing() {
+    let server = MockServer::start(respond);
+    let gitlab = server.gitlab();
+    let cache = Cache::in_memory().unwrap();
+    let report = TriageQuery::new()
+        .offline(true)
+        .run(&gitlab, &cache)
+        .await
+        .unwrap();
+    assert!(report.open_issues_missing);
+    assert!(report.untriaged.is_empty());
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn rate_limits_name_gitlab() {
+    let server = MockServer::start(|_| {
+        (
+            429,
+            vec![("Retry-After", "60".to_owned())],
+            r#"{"message": "Retry later"}"#.to_owned(),
+        )
+    });
+    let gitlab = server.gitlab();
+    let cache = Cache::in_memory().unwrap();
+    let date = chrono::NaiveDate::from_ymd(2021, 6, 5);
+    let error = Issues::for_date(&gitlab, &cache, date)
+        .await
+        .err()
+        .expect("rate limits are errors");
+    assert!(matches!(error, Error::RateLimited(limit) if limit.forge == "GitLab"));
+    assert!(error
+        .to_string()
+        .starts_with("hit GitLab primary rate limit"));
+}